@@ -0,0 +1,489 @@
+use crate::tokenize::TokenizerOptions;
+use regex::Regex;
+use std::collections::HashMap;
+use std::error::Error;
+use std::str::FromStr;
+use whatlang::Lang;
+
+/// How often, and how patiently, to retry opening the data file after a transient I/O error
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first one
+    pub max_attempts: usize,
+    /// Delay before the second attempt; doubled for every attempt after that
+    pub backoff_ms: u64,
+}
+
+impl RetryPolicy {
+    /// Upper bound for `--retry-attempts`
+    pub const MAX_ATTEMPTS: usize = 10;
+    /// Longest wait between two attempts, however many attempts came before
+    pub const MAX_DELAY_MS: u64 = 30_000;
+
+    /// Delay before retrying after failed attempt number `attempt` (counting from 1), capped at `MAX_DELAY_MS`
+    pub fn delay_ms(&self, attempt: usize) -> u64 {
+        let factor = u32::try_from(attempt.saturating_sub(1))
+            .ok()
+            .and_then(|shift| 1u64.checked_shl(shift))
+            .unwrap_or(u64::MAX);
+        self.backoff_ms
+            .saturating_mul(factor)
+            .min(Self::MAX_DELAY_MS)
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            backoff_ms: 100,
+        }
+    }
+}
+
+/// Which terms `--max-vocab` keeps
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum VocabularyRank {
+    /// The rarest terms (highest IDF), which discriminate best between questions
+    Idf,
+    /// The terms found in the most questions (highest document frequency), which match the most queries
+    DocumentFrequency,
+}
+
+impl FromStr for VocabularyRank {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "idf" => Ok(VocabularyRank::Idf),
+            "df" => Ok(VocabularyRank::DocumentFrequency),
+            _ => Err(format!("unknown vocabulary rank '{}'", s)),
+        }
+    }
+}
+
+/// Output format of `--batch` results
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Just the answer, as a line-oriented pipe: one query in, one answer out
+    Text,
+    /// One JSON object per line
+    Jsonl,
+    /// Comma-separated values with a header row
+    Csv,
+    /// Tab-separated values with a header row
+    Tsv,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(OutputFormat::Text),
+            "jsonl" => Ok(OutputFormat::Jsonl),
+            "csv" => Ok(OutputFormat::Csv),
+            "tsv" => Ok(OutputFormat::Tsv),
+            _ => Err(format!("unknown format '{}'", s)),
+        }
+    }
+}
+
+/// Runtime options for the agent, populated from command-line arguments
+pub struct Config {
+    /// Print the version and build information and exit
+    pub show_version: bool,
+    /// Path of the Q&A data file (`.json` or `.jsonl`)
+    pub data_path: String,
+    /// Reject data file entries with unknown fields instead of ignoring them
+    pub strict_parse: bool,
+    /// When set, the loaded Q&A pairs are written to this path as JSON-Lines and the program exits
+    pub export_jsonl: Option<String>,
+    /// When set, the Q&A pairs are written to this path as JSON-Lines with their TF-IDF vectors and the program exits
+    pub export_vectors: Option<String>,
+    /// Clean up answers at load time: collapse whitespace, turn dash variants into hyphens and trim
+    pub normalize_answers: bool,
+    /// When set, questions longer than this many tokens are split into one entry per sentence
+    pub split_long_questions: Option<usize>,
+    /// Collapse questions sharing an identical answer down to one canonical question
+    pub dedupe_answers: bool,
+    /// Retry behaviour when the data file cannot be opened
+    pub retry_policy: RetryPolicy,
+    /// Similarity a question must exceed for its answer to be given
+    pub threshold: f64,
+    /// Only consider questions that contain every token of the query (AND) instead of any of them (OR)
+    pub match_all: bool,
+    /// How questions and queries are split into tokens
+    pub tokenizer: TokenizerOptions,
+    /// Also match queries against the answer text, scoring each entry by the better of its question and its answer
+    pub index_answers: bool,
+    /// Highest score a match on the answer text alone can reach, against 1.0 for a perfect question match
+    pub answer_field_weight: f64,
+    /// Factors multiplying the TF-IDF weight of key terms in the questions, e.g. "pricing" => 2.0
+    pub term_boost: HashMap<String, f64>,
+    /// Base of the logarithm in IDF; e by default
+    pub idf_log_base: f64,
+    /// Cap on the vocabulary size; only the top terms by `vocabulary_rank` are kept
+    pub max_vocabulary_size: Option<usize>,
+    /// How terms are ranked when the vocabulary is capped
+    pub vocabulary_rank: VocabularyRank,
+    /// Factor multiplying the query weight of words marked as important, like `*refund*`
+    pub emphasis_weight: f64,
+    /// Cap on how many times a word repeated in the query is counted
+    pub max_tf: Option<usize>,
+    /// Weight (0.0 to 1.0) of the Soundex similarity blended into the word similarity; 0.0 disables it
+    pub phonetic_weight: f64,
+    /// Weight (0.0 to 1.0) of the in-order token overlap blended into the score; 0.0 disables it
+    pub subsequence_weight: f64,
+    /// When set, ties go to the question with the longer answer, counting at most this many characters
+    pub prefer_longer_answers: Option<usize>,
+    /// When no question matches, look for the query's words in the answer text before giving up
+    pub answer_search_fallback: bool,
+    /// As a last resort, give the entry whose question or answer literally contains the most query keywords
+    pub keyword_fallback: bool,
+    /// When set, answers detected to be in another language are withheld
+    pub answer_language: Option<Lang>,
+    /// When set, matched answers are shown through this template, with `{answer}` replaced by the answer
+    pub answer_template: Option<String>,
+    /// Show a confident answer as a "Q: <matched question>" / "A: <answer>" block
+    pub echo_question: bool,
+    /// How many of the closest questions to suggest when nothing matches well enough
+    pub suggestions: usize,
+    /// Rank suggestions by score divided by how many questions share the answer, favouring distinct answers
+    pub diversify_suggestions: bool,
+    /// Print corpus statistics as a JSON object and exit
+    pub stats_json: bool,
+    /// When set, print this many of the highest and lowest IDF terms and exit
+    pub idf_table: Option<usize>,
+    /// Print the question-by-question similarity matrix as CSV and exit
+    pub similarity_matrix: bool,
+    /// When set, print this many of the most similar question pairs and exit
+    pub similar_pairs: Option<usize>,
+    /// When set, print a Graphviz graph linking questions at least this similar and exit
+    pub dot_graph: Option<f64>,
+    /// When set, answer every query in this file (one per line, `-` for stdin) in `output_format` and exit
+    pub batch_file: Option<String>,
+    /// Output format of batch results
+    pub output_format: OutputFormat,
+    /// When set, check how many queries in this file (one per line) are answered with confidence and exit
+    pub coverage_file: Option<String>,
+    /// Coverage fraction below which the coverage check exits with a failure status
+    pub min_coverage: f64,
+    /// When set, measure retrieval quality with this many folds of cross-validation and exit
+    pub cross_validate: Option<usize>,
+    /// Ask every stored question verbatim, report those that don't resolve to themselves and exit
+    pub self_test: bool,
+    /// Number of self-test failures tolerated before the self-test exits with a failure status
+    pub self_test_max_failures: usize,
+    /// Patterns whose matches are replaced in queries before they are kept for the transcript
+    pub redact_patterns: Vec<Regex>,
+    /// Text replacing each redacted match
+    pub redact_placeholder: String,
+    /// Text printed before reading each question; an empty prompt prints nothing
+    pub prompt: String,
+    /// File of questions, one per line, answered before the interactive prompt starts
+    pub input_file: Option<String>,
+    /// Corpora with fewer questions than this trigger a warning (or an error in strict mode)
+    pub min_questions: usize,
+    /// Turn startup warnings into errors
+    pub strict: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            show_version: false,
+            data_path: "qa_data.json".to_string(),
+            strict_parse: false,
+            export_jsonl: None,
+            export_vectors: None,
+            normalize_answers: false,
+            split_long_questions: None,
+            dedupe_answers: false,
+            retry_policy: RetryPolicy::default(),
+            threshold: 0.5,
+            match_all: false,
+            tokenizer: TokenizerOptions::default(),
+            index_answers: false,
+            answer_field_weight: 0.6,
+            term_boost: HashMap::new(),
+            idf_log_base: std::f64::consts::E,
+            max_vocabulary_size: None,
+            vocabulary_rank: VocabularyRank::Idf,
+            emphasis_weight: 2.0,
+            max_tf: None,
+            phonetic_weight: 0.0,
+            subsequence_weight: 0.0,
+            prefer_longer_answers: None,
+            answer_search_fallback: false,
+            keyword_fallback: false,
+            answer_language: None,
+            answer_template: None,
+            echo_question: false,
+            suggestions: 1,
+            diversify_suggestions: false,
+            stats_json: false,
+            idf_table: None,
+            similarity_matrix: false,
+            similar_pairs: None,
+            dot_graph: None,
+            batch_file: None,
+            output_format: OutputFormat::Jsonl,
+            coverage_file: None,
+            min_coverage: 0.9,
+            cross_validate: None,
+            self_test: false,
+            self_test_max_failures: 0,
+            redact_patterns: Vec::new(),
+            redact_placeholder: "[REDACTED]".to_string(),
+            prompt: "> ".to_string(),
+            input_file: None,
+            min_questions: 3,
+            strict: false,
+        }
+    }
+}
+
+impl Config {
+    /// Parses command-line arguments (without the program name) into a Config
+    pub fn from_args<I: IntoIterator<Item = String>>(args: I) -> Result<Config, Box<dyn Error>> {
+        let mut config = Config::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--version" => config.show_version = true,
+                "--data" => config.data_path = next_value(&mut args, &arg)?,
+                "--strict-parse" => config.strict_parse = true,
+                "--export-jsonl" => config.export_jsonl = Some(next_value(&mut args, &arg)?),
+                "--export-vectors" => config.export_vectors = Some(next_value(&mut args, &arg)?),
+                "--min-questions" => config.min_questions = next_parsed(&mut args, &arg)?,
+                "--strict" => config.strict = true,
+                "--input-file" => config.input_file = Some(next_value(&mut args, &arg)?),
+                "--redact" => {
+                    let pattern = next_value(&mut args, &arg)?;
+                    config.redact_patterns.push(
+                        Regex::new(&pattern)
+                            .map_err(|err| format!("invalid '--redact' pattern: {}", err))?,
+                    );
+                }
+                "--redact-placeholder" => config.redact_placeholder = next_value(&mut args, &arg)?,
+                "--prompt" => config.prompt = next_value(&mut args, &arg)?,
+                "--stats-json" => config.stats_json = true,
+                "--idf-table" => config.idf_table = Some(next_parsed(&mut args, &arg)?),
+                "--similarity-matrix" => config.similarity_matrix = true,
+                "--similar-pairs" => config.similar_pairs = Some(next_parsed(&mut args, &arg)?),
+                "--dot-graph" => config.dot_graph = Some(next_parsed(&mut args, &arg)?),
+                "--normalize-answers" => config.normalize_answers = true,
+                "--split-long-questions" => {
+                    config.split_long_questions = Some(next_parsed(&mut args, &arg)?)
+                }
+                "--dedupe-answers" => config.dedupe_answers = true,
+                "--threshold" => config.threshold = next_parsed(&mut args, &arg)?,
+                "--batch" => config.batch_file = Some(next_value(&mut args, &arg)?),
+                "--format" => config.output_format = next_parsed(&mut args, &arg)?,
+                "--coverage" => config.coverage_file = Some(next_value(&mut args, &arg)?),
+                "--min-coverage" => config.min_coverage = next_parsed(&mut args, &arg)?,
+                "--cross-validate" => config.cross_validate = Some(next_parsed(&mut args, &arg)?),
+                "--self-test" => config.self_test = true,
+                "--self-test-max-failures" => {
+                    config.self_test_max_failures = next_parsed(&mut args, &arg)?
+                }
+                "--match-all" => config.match_all = true,
+                "--tokenizer" => config.tokenizer.strategy = next_parsed(&mut args, &arg)?,
+                "--expand-contractions" => config.tokenizer.expand_contractions = true,
+                "--normalize-emoji" => config.tokenizer.normalize_emoji = true,
+                "--index-answers" => config.index_answers = true,
+                "--answer-field-weight" => {
+                    config.answer_field_weight = next_parsed(&mut args, &arg)?
+                }
+                "--boost" => {
+                    let value = next_value(&mut args, &arg)?;
+                    let (term, factor) = value
+                        .split_once('=')
+                        .ok_or("'--boost' expects <term>=<factor>")?;
+                    let factor: f64 = factor
+                        .parse()
+                        .map_err(|_| format!("invalid boost factor '{}'", factor))?;
+                    config.term_boost.insert(term.to_lowercase(), factor);
+                }
+                "--idf-log-base" => {
+                    let value = next_value(&mut args, &arg)?;
+                    config.idf_log_base = match value.as_str() {
+                        "e" => std::f64::consts::E,
+                        _ => value
+                            .parse()
+                            .ok()
+                            .filter(|base: &f64| *base > 1.0)
+                            .ok_or_else(|| {
+                                format!(
+                                    "'--idf-log-base' must be 'e' or a number above 1, got '{}'",
+                                    value
+                                )
+                            })?,
+                    };
+                }
+                "--max-vocab" => config.max_vocabulary_size = Some(next_parsed(&mut args, &arg)?),
+                "--vocab-rank" => config.vocabulary_rank = next_parsed(&mut args, &arg)?,
+                "--emphasis-weight" => config.emphasis_weight = next_parsed(&mut args, &arg)?,
+                "--max-tf" => config.max_tf = Some(next_parsed(&mut args, &arg)?),
+                "--phonetic-weight" => {
+                    config.phonetic_weight = next_parsed(&mut args, &arg)?;
+                    if !(0.0..=1.0).contains(&config.phonetic_weight) {
+                        return Err("'--phonetic-weight' must be between 0.0 and 1.0".into());
+                    }
+                }
+                "--subsequence-weight" => {
+                    config.subsequence_weight = next_parsed(&mut args, &arg)?;
+                    if !(0.0..=1.0).contains(&config.subsequence_weight) {
+                        return Err("'--subsequence-weight' must be between 0.0 and 1.0".into());
+                    }
+                }
+                "--prefer-longer-answers" => {
+                    config.prefer_longer_answers = Some(next_parsed(&mut args, &arg)?)
+                }
+                "--answer-search-fallback" => config.answer_search_fallback = true,
+                "--keyword-fallback" => config.keyword_fallback = true,
+                "--answer-language" => {
+                    let code = next_value(&mut args, &arg)?;
+                    config.answer_language = Some(
+                        parse_language(&code)
+                            .ok_or_else(|| format!("unsupported language '{}'", code))?,
+                    );
+                }
+                "--answer-template" => {
+                    let template = next_value(&mut args, &arg)?.replace("\\n", "\n");
+                    if !template.contains("{answer}") {
+                        return Err("'--answer-template' must contain {answer}".into());
+                    }
+                    config.answer_template = Some(template);
+                }
+                "--diversify-suggestions" => config.diversify_suggestions = true,
+                "--echo-question" => config.echo_question = true,
+                "--suggestions" => config.suggestions = next_parsed(&mut args, &arg)?,
+                "--retry-attempts" => {
+                    config.retry_policy.max_attempts = next_parsed(&mut args, &arg)?;
+                    if !(1..=RetryPolicy::MAX_ATTEMPTS).contains(&config.retry_policy.max_attempts)
+                    {
+                        return Err(format!(
+                            "'--retry-attempts' must be between 1 and {}",
+                            RetryPolicy::MAX_ATTEMPTS
+                        )
+                        .into());
+                    }
+                }
+                "--retry-backoff-ms" => {
+                    config.retry_policy.backoff_ms = next_parsed(&mut args, &arg)?
+                }
+                _ => return Err(format!("unknown argument '{}'", arg).into()),
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+/// Returns the value following a flag, or an error naming the flag if it is missing
+fn next_value<I: Iterator<Item = String>>(
+    args: &mut I,
+    flag: &str,
+) -> Result<String, Box<dyn Error>> {
+    args.next()
+        .ok_or_else(|| format!("missing value for '{}'", flag).into())
+}
+
+/// Returns the value following a flag parsed as `T`, or an error naming the flag if it is missing or invalid
+fn next_parsed<T: FromStr, I: Iterator<Item = String>>(
+    args: &mut I,
+    flag: &str,
+) -> Result<T, Box<dyn Error>> {
+    let value = next_value(args, flag)?;
+    value
+        .parse()
+        .map_err(|_| format!("invalid value '{}' for '{}'", value, flag).into())
+}
+
+/// Parses a BCP-47 tag such as "en" or "pt-BR" (or an ISO 639-3 code such as "eng") by its primary language
+fn parse_language(tag: &str) -> Option<Lang> {
+    let primary = tag.split(['-', '_']).next()?.to_lowercase();
+    let code = match primary.as_str() {
+        "af" => "afr",
+        "ar" => "ara",
+        "bg" => "bul",
+        "bn" => "ben",
+        "cs" => "ces",
+        "da" => "dan",
+        "de" => "deu",
+        "el" => "ell",
+        "en" => "eng",
+        "es" => "spa",
+        "fa" => "pes",
+        "fi" => "fin",
+        "fr" => "fra",
+        "he" => "heb",
+        "hi" => "hin",
+        "hu" => "hun",
+        "id" => "ind",
+        "it" => "ita",
+        "ja" => "jpn",
+        "ko" => "kor",
+        "nl" => "nld",
+        "pl" => "pol",
+        "pt" => "por",
+        "ro" => "ron",
+        "ru" => "rus",
+        "sv" => "swe",
+        "th" => "tha",
+        "tr" => "tur",
+        "uk" => "ukr",
+        "vi" => "vie",
+        "zh" => "cmn",
+        other => other,
+    };
+    Lang::from_code(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn language_tags_parse_by_primary_language() {
+        assert_eq!(parse_language("en"), Some(Lang::Eng));
+        assert_eq!(parse_language("pt-BR"), Some(Lang::Por));
+        assert_eq!(parse_language("spa"), Some(Lang::Spa));
+        assert_eq!(parse_language("xx"), None);
+    }
+
+    #[test]
+    fn answer_template_must_contain_the_answer() {
+        let parse = |template: &str| {
+            Config::from_args(["--answer-template".to_string(), template.to_string()])
+        };
+        assert_eq!(
+            parse("{answer}\\n-- Thoughtful AI")
+                .unwrap()
+                .answer_template
+                .as_deref(),
+            Some("{answer}\n-- Thoughtful AI")
+        );
+        assert!(parse("-- Thoughtful AI").is_err());
+    }
+
+    #[test]
+    fn retry_delays_double_up_to_a_cap() {
+        let policy = RetryPolicy {
+            max_attempts: RetryPolicy::MAX_ATTEMPTS,
+            backoff_ms: 100,
+        };
+        assert_eq!(policy.delay_ms(1), 100);
+        assert_eq!(policy.delay_ms(3), 400);
+        assert_eq!(policy.delay_ms(70), RetryPolicy::MAX_DELAY_MS);
+
+        let parse = |attempts: &str| {
+            Config::from_args(["--retry-attempts".to_string(), attempts.to_string()])
+        };
+        assert_eq!(parse("10").unwrap().retry_policy.max_attempts, 10);
+        assert!(parse("0").is_err());
+        assert!(parse("1000").is_err());
+    }
+}
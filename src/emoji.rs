@@ -0,0 +1,339 @@
+/// The most common emoji with their CLDR short names, so "how does 🤖 work?" can match questions about robots
+const EMOJI_NAMES: &[(char, &str)] = &[
+    // Smileys and emotion
+    ('😀', "grinning face"),
+    ('😃', "grinning face with big eyes"),
+    ('😄', "grinning face with smiling eyes"),
+    ('😁', "beaming face with smiling eyes"),
+    ('😆', "grinning squinting face"),
+    ('😅', "grinning face with sweat"),
+    ('🤣', "rolling on the floor laughing"),
+    ('😂', "face with tears of joy"),
+    ('🙂', "slightly smiling face"),
+    ('🙃', "upside down face"),
+    ('😉', "winking face"),
+    ('😊', "smiling face with smiling eyes"),
+    ('😇', "smiling face with halo"),
+    ('🥰', "smiling face with hearts"),
+    ('😍', "smiling face with heart eyes"),
+    ('🤩', "star struck"),
+    ('😘', "face blowing a kiss"),
+    ('😋', "face savoring food"),
+    ('😛', "face with tongue"),
+    ('😜', "winking face with tongue"),
+    ('🤪', "zany face"),
+    ('🤑', "money mouth face"),
+    ('🤗', "smiling face with open hands"),
+    ('🤭', "face with hand over mouth"),
+    ('🤫', "shushing face"),
+    ('🤔', "thinking face"),
+    ('🤐', "zipper mouth face"),
+    ('🤨', "face with raised eyebrow"),
+    ('😐', "neutral face"),
+    ('😑', "expressionless face"),
+    ('😶', "face without mouth"),
+    ('😏', "smirking face"),
+    ('😒', "unamused face"),
+    ('🙄', "face with rolling eyes"),
+    ('😬', "grimacing face"),
+    ('🤥', "lying face"),
+    ('😌', "relieved face"),
+    ('😔', "pensive face"),
+    ('😪', "sleepy face"),
+    ('🤤', "drooling face"),
+    ('😴', "sleeping face"),
+    ('😷', "face with medical mask"),
+    ('🤒', "face with thermometer"),
+    ('🤕', "face with head bandage"),
+    ('🤢', "nauseated face"),
+    ('🤮', "face vomiting"),
+    ('🥵', "hot face"),
+    ('🥶', "cold face"),
+    ('😵', "face with crossed out eyes"),
+    ('🤯', "exploding head"),
+    ('🥳', "partying face"),
+    ('😎', "smiling face with sunglasses"),
+    ('🤓', "nerd face"),
+    ('🧐', "face with monocle"),
+    ('😕', "confused face"),
+    ('😟', "worried face"),
+    ('🙁', "slightly frowning face"),
+    ('😮', "face with open mouth"),
+    ('😲', "astonished face"),
+    ('😳', "flushed face"),
+    ('🥺', "pleading face"),
+    ('😨', "fearful face"),
+    ('😰', "anxious face with sweat"),
+    ('😢', "crying face"),
+    ('😭', "loudly crying face"),
+    ('😱', "face screaming in fear"),
+    ('😖', "confounded face"),
+    ('😣', "persevering face"),
+    ('😞', "disappointed face"),
+    ('😓', "downcast face with sweat"),
+    ('😩', "weary face"),
+    ('😫', "tired face"),
+    ('🥱', "yawning face"),
+    ('😤', "face with steam from nose"),
+    ('😡', "enraged face"),
+    ('😠', "angry face"),
+    ('🤬', "face with symbols on mouth"),
+    ('😈', "smiling face with horns"),
+    ('💀', "skull"),
+    ('💩', "pile of poo"),
+    ('🤡', "clown face"),
+    ('👻', "ghost"),
+    ('👽', "alien"),
+    ('🤖', "robot"),
+    ('😺', "grinning cat"),
+    ('🙈', "see no evil monkey"),
+    ('💋', "kiss mark"),
+    ('💌', "love letter"),
+    ('💘', "heart with arrow"),
+    ('💔', "broken heart"),
+    ('💕', "two hearts"),
+    ('💖', "sparkling heart"),
+    ('💙', "blue heart"),
+    ('💚', "green heart"),
+    ('💛', "yellow heart"),
+    ('💜', "purple heart"),
+    ('🖤', "black heart"),
+    ('❤', "red heart"),
+    ('💯', "hundred points"),
+    ('💢', "anger symbol"),
+    ('💥', "collision"),
+    ('💦', "sweat droplets"),
+    ('💤', "zzz"),
+    ('💬', "speech balloon"),
+    ('💭', "thought balloon"),
+    // People and body
+    ('👋', "waving hand"),
+    ('🤚', "raised back of hand"),
+    ('✋', "raised hand"),
+    ('👌', "ok hand"),
+    ('🤞', "crossed fingers"),
+    ('✌', "victory hand"),
+    ('🤟', "love you gesture"),
+    ('🤘', "sign of the horns"),
+    ('🤙', "call me hand"),
+    ('👈', "backhand index pointing left"),
+    ('👉', "backhand index pointing right"),
+    ('👆', "backhand index pointing up"),
+    ('👇', "backhand index pointing down"),
+    ('☝', "index pointing up"),
+    ('👍', "thumbs up"),
+    ('👎', "thumbs down"),
+    ('✊', "raised fist"),
+    ('👊', "oncoming fist"),
+    ('👏', "clapping hands"),
+    ('🙌', "raising hands"),
+    ('🙏', "folded hands"),
+    ('🤝', "handshake"),
+    ('💪', "flexed biceps"),
+    ('✍', "writing hand"),
+    ('👀', "eyes"),
+    ('🧠', "brain"),
+    ('👤', "bust in silhouette"),
+    ('👥', "busts in silhouette"),
+    ('👶', "baby"),
+    ('👨', "man"),
+    ('👩', "woman"),
+    ('🧑', "person"),
+    ('🤷', "person shrugging"),
+    ('🤦', "person facepalming"),
+    ('🙋', "person raising hand"),
+    // Animals and nature
+    ('🐶', "dog face"),
+    ('🐱', "cat face"),
+    ('🐭', "mouse face"),
+    ('🐰', "rabbit face"),
+    ('🦊', "fox"),
+    ('🐻', "bear"),
+    ('🐼', "panda"),
+    ('🐸', "frog"),
+    ('🐵', "monkey face"),
+    ('🐔', "chicken"),
+    ('🐧', "penguin"),
+    ('🐦', "bird"),
+    ('🦄', "unicorn"),
+    ('🐝', "honeybee"),
+    ('🐛', "bug"),
+    ('🦋', "butterfly"),
+    ('🐢', "turtle"),
+    ('🐍', "snake"),
+    ('🐙', "octopus"),
+    ('🐳', "spouting whale"),
+    ('🌸', "cherry blossom"),
+    ('🌹', "rose"),
+    ('🌻', "sunflower"),
+    ('🌱', "seedling"),
+    ('🌲', "evergreen tree"),
+    ('🍀', "four leaf clover"),
+    ('🍁', "maple leaf"),
+    ('🌍', "globe showing europe africa"),
+    ('🌎', "globe showing americas"),
+    ('🌏', "globe showing asia australia"),
+    ('🌐', "globe with meridians"),
+    ('🌙', "crescent moon"),
+    ('🌞', "sun with face"),
+    ('☀', "sun"),
+    ('⭐', "star"),
+    ('🌟', "glowing star"),
+    ('☁', "cloud"),
+    ('⛅', "sun behind cloud"),
+    ('🌧', "cloud with rain"),
+    ('⚡', "high voltage"),
+    ('❄', "snowflake"),
+    ('🔥', "fire"),
+    ('💧', "droplet"),
+    ('🌊', "water wave"),
+    ('🌈', "rainbow"),
+    // Food and drink
+    ('🍎', "red apple"),
+    ('🍌', "banana"),
+    ('🍕', "pizza"),
+    ('🍔', "hamburger"),
+    ('🍟', "french fries"),
+    ('🍰', "shortcake"),
+    ('🎂', "birthday cake"),
+    ('🍪', "cookie"),
+    ('🍫', "chocolate bar"),
+    ('☕', "hot beverage"),
+    ('🍵', "teacup without handle"),
+    ('🍺', "beer mug"),
+    ('🍷', "wine glass"),
+    ('🥂', "clinking glasses"),
+    // Travel and places
+    ('🏠', "house"),
+    ('🏢', "office building"),
+    ('🏥', "hospital"),
+    ('🏦', "bank"),
+    ('🏫', "school"),
+    ('🚗', "automobile"),
+    ('🚕', "taxi"),
+    ('🚌', "bus"),
+    ('🚑', "ambulance"),
+    ('🚚', "delivery truck"),
+    ('🚲', "bicycle"),
+    ('✈', "airplane"),
+    ('🚀', "rocket"),
+    ('⏰', "alarm clock"),
+    ('⌛', "hourglass done"),
+    ('⏳', "hourglass not done"),
+    ('⌚', "watch"),
+    // Activities
+    ('🎉', "party popper"),
+    ('🎊', "confetti ball"),
+    ('🎁', "wrapped gift"),
+    ('🏆', "trophy"),
+    ('🥇', "first place medal"),
+    ('⚽', "soccer ball"),
+    ('🎮', "video game"),
+    ('🎯', "bullseye"),
+    ('🎵', "musical note"),
+    ('🎧', "headphone"),
+    // Objects
+    ('📱', "mobile phone"),
+    ('📞', "telephone receiver"),
+    ('☎', "telephone"),
+    ('💻', "laptop"),
+    ('🖥', "desktop computer"),
+    ('🖨', "printer"),
+    ('⌨', "keyboard"),
+    ('🖱', "computer mouse"),
+    ('💾', "floppy disk"),
+    ('💿', "optical disk"),
+    ('📷', "camera"),
+    ('📺', "television"),
+    ('🔋', "battery"),
+    ('🔌', "electric plug"),
+    ('💡', "light bulb"),
+    ('🔦', "flashlight"),
+    ('📖', "open book"),
+    ('📚', "books"),
+    ('📝', "memo"),
+    ('📄', "page facing up"),
+    ('📃', "page with curl"),
+    ('📋', "clipboard"),
+    ('📁', "file folder"),
+    ('📂', "open file folder"),
+    ('📅', "calendar"),
+    ('📆', "tear off calendar"),
+    ('📈', "chart increasing"),
+    ('📉', "chart decreasing"),
+    ('📊', "bar chart"),
+    ('📌', "pushpin"),
+    ('📎', "paperclip"),
+    ('✂', "scissors"),
+    ('🔒', "locked"),
+    ('🔓', "unlocked"),
+    ('🔑', "key"),
+    ('🔨', "hammer"),
+    ('🔧', "wrench"),
+    ('⚙', "gear"),
+    ('🔗', "link"),
+    ('🧰', "toolbox"),
+    ('💊', "pill"),
+    ('💉', "syringe"),
+    ('🩺', "stethoscope"),
+    ('🧾', "receipt"),
+    ('💰', "money bag"),
+    ('💵', "dollar banknote"),
+    ('💳', "credit card"),
+    ('💸', "money with wings"),
+    ('✉', "envelope"),
+    ('📧', "e mail"),
+    ('📨', "incoming envelope"),
+    ('📦', "package"),
+    ('📢', "loudspeaker"),
+    ('🔔', "bell"),
+    ('🔍', "magnifying glass tilted left"),
+    ('🔎', "magnifying glass tilted right"),
+    // Symbols
+    ('✅', "check mark button"),
+    ('✔', "check mark"),
+    ('❌', "cross mark"),
+    ('❓', "red question mark"),
+    ('❔', "white question mark"),
+    ('❗', "red exclamation mark"),
+    ('⚠', "warning"),
+    ('⛔', "no entry"),
+    ('🚫', "prohibited"),
+    ('♻', "recycling symbol"),
+    ('➕', "plus"),
+    ('➖', "minus"),
+    ('➡', "right arrow"),
+    ('⬅', "left arrow"),
+    ('⬆', "up arrow"),
+    ('⬇', "down arrow"),
+    ('🔄', "counterclockwise arrows button"),
+    ('🆕', "new button"),
+    ('🆗', "ok button"),
+    ('🆘', "sos button"),
+    ('ℹ', "information"),
+    ('🔴', "red circle"),
+    ('🟢', "green circle"),
+    ('✨', "sparkles"),
+];
+
+/// Returns the CLDR short name of a common emoji, or None if it is not in the built-in table
+pub fn emoji_name(c: char) -> Option<&'static str> {
+    EMOJI_NAMES
+        .iter()
+        .find(|(emoji, _)| *emoji == c)
+        .map(|(_, name)| *name)
+}
+
+/// Whether `c` is an emoji codepoint or one of the invisible characters that build up emoji sequences
+/// (variation selectors, zero-width joiner, skin tone modifiers, keycap and tag characters)
+pub fn is_emoji(c: char) -> bool {
+    matches!(c,
+        '\u{1F000}'..='\u{1FAFF}'
+        | '\u{2600}'..='\u{27BF}'
+        | '\u{2B00}'..='\u{2BFF}'
+        | '\u{2300}'..='\u{23FF}'
+        | '\u{FE00}'..='\u{FE0F}'
+        | '\u{200D}'
+        | '\u{20E3}'
+        | '\u{E0020}'..='\u{E007F}')
+}
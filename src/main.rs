@@ -1,176 +1,4576 @@
-use serde_json::Value;
+mod config;
+mod emoji;
+mod tokenize;
+
+use config::{Config, OutputFormat, RetryPolicy, VocabularyRank};
+use serde_json::{json, Value};
 use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::fs::File;
-use std::io::{self, BufReader, Write};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::process;
+use std::thread;
+use std::time::Duration;
+use tokenize::{phonetic_tokenize, strip_emphasis, tokenize, TokenizerOptions};
+use whatlang::Lang;
+
+/// Function splitting text into index tokens, either plain words or their phonetic codes
+type Tokenizer = fn(&str, &TokenizerOptions) -> Vec<String>;
+
+/// Per-question TF-IDF vectors together with the IDF scores they were built from
+type TfidfIndex = (HashMap<String, HashMap<String, f64>>, HashMap<String, f64>);
+
+/// Q&A pairs keyed by question, aliases included, with the map from each alias to the question it rephrases
+type Corpus = (HashMap<String, String>, HashMap<String, String>);
+
+/// Like `print!`, but exits quietly when stdout has been closed instead of panicking
+macro_rules! out {
+    ($($arg:tt)*) => {
+        write_stdout(format_args!($($arg)*))
+    };
+}
+
+/// Like `println!`, but exits quietly when stdout has been closed instead of panicking
+macro_rules! outln {
+    () => {
+        out!("\n")
+    };
+    ($($arg:tt)*) => {
+        write_stdout(format_args!("{}\n", format_args!($($arg)*)))
+    };
+}
 
 /// Main function: Initializes QA data, computes TF-IDF, and runs the interactive question-answering loop
 fn main() {
-    let qa_data = initialize_qa_data().unwrap();
-    let (tfidf_vectors, idf) = compute_tfidf(&qa_data);
+    let config = Config::from_args(std::env::args().skip(1)).unwrap_or_else(|err| {
+        eprintln!("Error: {}", err);
+        process::exit(2);
+    });
 
-    println!("Welcome to the Thoughtful AI Customer Support Agent!");
-    println!("Ask a question about Thoughtful AI (type 'exit' to quit):");
+    if config.show_version {
+        outln!(
+            "{} {} (commit {}, built {})",
+            env!("CARGO_PKG_NAME"),
+            env!("CARGO_PKG_VERSION"),
+            env!("GIT_COMMIT"),
+            env!("BUILD_TIMESTAMP")
+        );
+        return;
+    }
 
-    loop {
-        print!("> ");
-        io::stdout().flush().unwrap();
+    let ((mut qa_data, mut aliases), mut precomputed_index) =
+        initialize_qa_data(&config.data_path, &config.retry_policy, config.strict_parse)
+            .unwrap_or_else(|err| {
+                eprintln!("Error: could not load {}: {}", config.data_path, err);
+                process::exit(1);
+            });
 
-        let mut input = String::new();
-        io::stdin().read_line(&mut input).unwrap();
-        let input = input.trim();
+    if config.normalize_answers {
+        for answer in qa_data.values_mut() {
+            *answer = normalize_answer(answer);
+        }
+    }
 
-        if input.eq_ignore_ascii_case("exit") {
-            println!("Thank you for using the Thoughtful AI Customer Support Agent. Goodbye!");
-            break;
+    if let Some(max_tokens) = config.split_long_questions {
+        let report =
+            split_long_questions(&mut qa_data, &mut aliases, &config.tokenizer, max_tokens);
+        if report.split_count > 0 {
+            precomputed_index = None;
+            outln!(
+                "Split {} long questions into {} entries",
+                report.split_count,
+                report.new_entries.len()
+            );
+            for (question, _) in &report.new_entries {
+                outln!("  {}", question);
+            }
         }
+    }
 
-        let response = get_response(&qa_data, &tfidf_vectors, &idf, input);
-        println!("{}", response);
+    if config.dedupe_answers {
+        let report = deduplicate_answers(&mut qa_data, &mut aliases);
+        if report.removed_count > 0 {
+            precomputed_index = None;
+        }
+        outln!(
+            "Removed {} questions with duplicate answers",
+            report.removed_count
+        );
+        for group in &report.groups {
+            outln!("  kept '{}', removed {:?}", group[0], &group[1..]);
+        }
     }
-}
 
-/// Initializes QA data by reading from a JSON file and parsing it into a HashMap
-fn initialize_qa_data() -> Result<HashMap<String, String>, Box<dyn std::error::Error>> {
-    // Open the file
-    let file = File::open("qa_data.json")?;
-    let reader = BufReader::new(file);
+    // IDF carries little information on a tiny corpus, so matching quality will be poor
+    let question_count = qa_data.len() - aliases.len();
+    if question_count < config.min_questions {
+        let message = format!(
+            "{} contains only {} questions (minimum {})",
+            config.data_path, question_count, config.min_questions
+        );
+        if config.strict {
+            eprintln!("Error: {}", message);
+            process::exit(1);
+        }
+        eprintln!("Warning: {}; answers may be unreliable", message);
+    }
 
-    // Parse the JSON
-    let json: Value = serde_json::from_reader(reader)?;
+    for group in find_normalized_duplicates(&qa_data, &aliases, &config.tokenizer) {
+        let message = format!("questions {:?} are probable duplicates", group);
+        if config.strict {
+            eprintln!("Error: {}", message);
+            process::exit(1);
+        }
+        eprintln!(
+            "Warning: {}; they have the same words once normalized",
+            message
+        );
+    }
 
-    // Create a HashMap to store the Q&A pairs
-    let mut qa_data = HashMap::new();
+    if let Some(path) = &config.export_jsonl {
+        export_or_exit(path, |writer| to_jsonl_stream(&qa_data, &aliases, writer));
+        outln!("Exported {} Q&A pairs to {}", question_count, path);
+        return;
+    }
 
-    // Extract the questions and answers from the JSON
-    if let Some(questions) = json["questions"].as_array() {
-        for q in questions {
-            if let (Some(question), Some(answer)) = (q["question"].as_str(), q["answer"].as_str()) {
-                qa_data.insert(question.to_string(), answer.to_string());
-            }
+    // An index loaded from an `--export-vectors` file was built, converted and truncated when it was exported
+    let imported = precomputed_index.is_some();
+    let (mut tfidf_vectors, mut idf) = precomputed_index.unwrap_or_else(|| {
+        compute_tfidf(
+            &qa_data,
+            Field::Question,
+            tokenize,
+            &config.tokenizer,
+            &config.term_boost,
+        )
+    });
+    // A single question gets a flat IDF of 1.0 rather than a logarithm, so there is nothing to convert
+    if config.idf_log_base != std::f64::consts::E && qa_data.len() > 1 && !imported {
+        change_idf_log_base(&mut tfidf_vectors, &mut idf, config.idf_log_base);
+    }
+    if let Some(max_terms) = config.max_vocabulary_size.filter(|_| !imported) {
+        let dropped = truncate_vocabulary(
+            &mut tfidf_vectors,
+            &mut idf,
+            max_terms,
+            config.vocabulary_rank,
+        );
+        if dropped > 0 {
+            eprintln!(
+                "Warning: vocabulary truncated to {} terms ({} terms dropped)",
+                max_terms, dropped
+            );
         }
     }
+    if let Some(path) = &config.export_vectors {
+        export_or_exit(path, |writer| {
+            to_jsonl_stream_with_vectors(&qa_data, &aliases, &tfidf_vectors, &idf, writer)
+        });
+        outln!(
+            "Exported {} Q&A pairs with TF-IDF vectors to {}",
+            question_count,
+            path
+        );
+        return;
+    }
+    if config.stats_json {
+        let summary = summarize_corpus(&qa_data, &aliases, &idf, &config.tokenizer);
+        outln!("{}", corpus_summary_json(&summary));
+        return;
+    }
 
-    Ok(qa_data)
-}
+    if let Some(top_n) = config.idf_table {
+        print_idf_table(&idf, top_n);
+        return;
+    }
 
-/// Computes TF-IDF vectors for all questions in the QA data
-/// Returns a tuple containing:
-/// 1. A HashMap of TF-IDF vectors for each question
-/// 2. The IDF (Inverse Document Frequency) scores for all words
-fn compute_tfidf(
-    qa_data: &HashMap<String, String>,
-) -> (HashMap<String, HashMap<String, f64>>, HashMap<String, f64>) {
-    let mut word_doc_count: HashMap<String, usize> = HashMap::new();
-    let mut tfidf_vectors: HashMap<String, HashMap<String, f64>> = HashMap::new();
+    if config.similarity_matrix {
+        let (questions, matrix) = cosine_similarity_matrix(&tfidf_vectors);
+        out!("{}", similarity_matrix_csv(&questions, &matrix));
+        return;
+    }
 
-    // Compute document frequency
-    for question in qa_data.keys() {
-        let words: HashSet<String> = question
-            .to_lowercase()
-            .split_whitespace()
-            .map(String::from)
-            .collect();
-        for word in words {
-            *word_doc_count.entry(word).or_insert(0) += 1;
+    if let Some(k) = config.similar_pairs {
+        let (questions, matrix) = cosine_similarity_matrix(&tfidf_vectors);
+        for (q1, q2, similarity) in most_similar_pairs(&questions, &matrix, k) {
+            outln!("{:.3}  '{}' / '{}'", display_score(similarity), q1, q2);
         }
+        return;
     }
 
-    // Compute IDF
-    let doc_count = qa_data.len() as f64;
-    let idf: HashMap<String, f64> = word_doc_count
-        .iter()
-        .map(|(word, count)| (word.clone(), (doc_count / *count as f64).ln()))
-        .collect();
+    if let Some(min_similarity) = config.dot_graph {
+        out!("{}", export_dot_graph(&tfidf_vectors, min_similarity));
+        return;
+    }
 
-    // Compute TF-IDF
-    for (question, _) in qa_data {
-        let mut tf: HashMap<String, usize> = HashMap::new();
-        let words: Vec<String> = question
-            .to_lowercase()
-            .split_whitespace()
+    let (phonetic_index, answer_index) = compute_secondary_indexes(&qa_data, &config);
+
+    let mut session = Session {
+        qa_data: &qa_data,
+        aliases: &aliases,
+        tfidf_vectors: &tfidf_vectors,
+        idf: &idf,
+        phonetic_index: phonetic_index.as_ref(),
+        answer_index: answer_index.as_ref(),
+        config: &config,
+        history: Vec::new(),
+    };
+
+    if let Some(path) = &config.batch_file {
+        let reader: Box<dyn BufRead> = if path == "-" {
+            Box::new(io::stdin().lock())
+        } else {
+            match File::open(path) {
+                Ok(file) => Box::new(BufReader::new(file)),
+                Err(err) => {
+                    eprintln!("Error: could not open {}: {}", path, err);
+                    process::exit(1);
+                }
+            }
+        };
+        if config.output_format == OutputFormat::Csv {
+            outln!("query,answer,matched_question,score");
+        } else if config.output_format == OutputFormat::Tsv {
+            outln!("query\tanswer\tmatched_question\tscore");
+        }
+        for (index, line) in reader.lines().enumerate() {
+            let query = line.unwrap_or_else(|err| {
+                let source = if path == "-" { "standard input" } else { path };
+                eprintln!(
+                    "Error: could not read line {} of {}: {}",
+                    index + 1,
+                    source,
+                    err
+                );
+                process::exit(1);
+            });
+            let query = query.trim();
+            if query.is_empty() {
+                continue;
+            }
+            let result = session.answer_query(query);
+            outln!("{}", format_batch_result(&result, config.output_format));
+        }
+        return;
+    }
+
+    if let Some(path) = &config.coverage_file {
+        let queries: Vec<String> = std::fs::read_to_string(path)
+            .unwrap_or_else(|err| {
+                eprintln!("Error: could not open {}: {}", path, err);
+                process::exit(1);
+            })
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
             .map(String::from)
             .collect();
-        for word in &words {
-            *tf.entry(word.clone()).or_insert(0) += 1;
+        let report = assert_coverage(
+            &qa_data,
+            &tfidf_vectors,
+            &idf,
+            phonetic_index.as_ref(),
+            answer_index.as_ref(),
+            &queries,
+            &config,
+        );
+
+        outln!(
+            "Covered {} of {} queries ({:.1}%)",
+            report.covered,
+            report.total,
+            report.coverage_rate() * 100.0
+        );
+        for query in &report.uncovered {
+            outln!("  not covered: {}", query);
+        }
+        if report.coverage_rate() < config.min_coverage {
+            process::exit(1);
         }
+        return;
+    }
 
-        let mut tfidf = HashMap::new();
-        for (word, count) in tf {
-            let tf = count as f64 / words.len() as f64;
-            let idf_value = idf.get(&word).unwrap_or(&0.0);
-            tfidf.insert(word, tf * idf_value);
+    if let Some(folds) = config.cross_validate {
+        if folds < 2 || folds > qa_data.len() {
+            eprintln!(
+                "Error: cross-validation needs between 2 and {} folds",
+                qa_data.len()
+            );
+            process::exit(2);
         }
-        tfidf_vectors.insert(question.clone(), tfidf);
+        let report = cross_validate(&qa_data, folds, &config);
+        outln!(
+            "{}-fold cross-validation: mean precision@1 {:.3}",
+            report.folds,
+            report.mean_precision_at_1
+        );
+        for (fold, score) in report.per_fold_scores.iter().enumerate() {
+            outln!("  fold {}: {:.3}", fold + 1, score);
+        }
+        return;
     }
 
-    (tfidf_vectors, idf)
+    if config.self_test {
+        let failures = self_test(
+            &qa_data,
+            &tfidf_vectors,
+            &idf,
+            phonetic_index.as_ref(),
+            answer_index.as_ref(),
+            &config,
+        );
+        outln!(
+            "{} of {} questions resolve to themselves",
+            qa_data.len() - failures.len(),
+            qa_data.len()
+        );
+        for failure in &failures {
+            match &failure.matched {
+                Some(other) => outln!(
+                    "  '{}' matched '{}' ({:.3})",
+                    failure.question,
+                    other,
+                    display_score(failure.similarity)
+                ),
+                None => outln!(
+                    "  '{}' scored only {:.3} against itself",
+                    failure.question,
+                    display_score(failure.similarity)
+                ),
+            }
+        }
+        if failures.len() > config.self_test_max_failures {
+            process::exit(1);
+        }
+        return;
+    }
+
+    outln!("Welcome to the Thoughtful AI Customer Support Agent!");
+    outln!("Ask a question about Thoughtful AI (type 'exit' to quit):");
+
+    // Replay scripted questions first, echoing each one as if it had been typed
+    if let Some(path) = &config.input_file {
+        let file = File::open(path).unwrap_or_else(|err| {
+            eprintln!("Error: could not open {}: {}", path, err);
+            process::exit(1);
+        });
+        for (index, line) in BufReader::new(file).lines().enumerate() {
+            let line = line.unwrap_or_else(|err| {
+                eprintln!(
+                    "Error: could not read line {} of {}: {}",
+                    index + 1,
+                    path,
+                    err
+                );
+                process::exit(1);
+            });
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            outln!("{}{}", config.prompt, line);
+            if !session.handle_line(line) {
+                return;
+            }
+        }
+    }
+
+    loop {
+        if !config.prompt.is_empty() {
+            out!("{}", config.prompt);
+            flush_stdout();
+        }
+
+        let mut input = String::new();
+        // Stop at end of input rather than answering empty lines forever
+        match io::stdin().read_line(&mut input) {
+            Ok(0) => {
+                outln!();
+                break;
+            }
+            Ok(_) => {}
+            Err(err) => {
+                eprintln!("Error: could not read standard input: {}", err);
+                process::exit(1);
+            }
+        }
+
+        if !session.handle_line(input.trim()) {
+            break;
+        }
+    }
 }
 
-/// Finds the best matching question for the given input and returns the corresponding answer
-fn get_response(
-    qa_data: &HashMap<String, String>,
-    tfidf_vectors: &HashMap<String, HashMap<String, f64>>,
-    idf: &HashMap<String, f64>,
-    input: &str,
-) -> String {
-    let input_vector = compute_input_vector(input, idf);
-    let mut best_match = String::new();
-    let mut max_similarity = f64::MIN;
+/// Writes to stdout, see `out!`
+fn write_stdout(args: fmt::Arguments) {
+    if let Err(err) = io::stdout().lock().write_fmt(args) {
+        stdout_failed(err);
+    }
+}
 
-    for (question, vector) in tfidf_vectors {
-        let similarity = cosine_similarity(&input_vector, vector);
-        if similarity > max_similarity {
-            max_similarity = similarity;
-            best_match = question.clone();
+/// Flushes stdout so a prompt without a newline is shown
+fn flush_stdout() {
+    if let Err(err) = io::stdout().flush() {
+        stdout_failed(err);
+    }
+}
+
+/// Exits with status 0 when the reader of stdout has gone away (e.g. output piped into `head`),
+/// as Unix tools conventionally do; any other write error (such as a full disk) is reported and exits with status 1
+fn stdout_failed(err: io::Error) -> ! {
+    if err.kind() == io::ErrorKind::BrokenPipe {
+        process::exit(0);
+    }
+    eprintln!("Error: could not write to stdout: {}", err);
+    process::exit(1);
+}
+
+/// State of one run answering queries, shared by typed input, `--input-file` scripts and `--batch`
+struct Session<'a> {
+    qa_data: &'a HashMap<String, String>,
+    /// Maps each alias in `qa_data` to the question it rephrases
+    aliases: &'a HashMap<String, String>,
+    tfidf_vectors: &'a HashMap<String, HashMap<String, f64>>,
+    idf: &'a HashMap<String, f64>,
+    phonetic_index: Option<&'a TfidfIndex>,
+    answer_index: Option<&'a TfidfIndex>,
+    config: &'a Config,
+    /// Every (question, response) exchanged this session, for `/transcript`
+    history: Vec<(String, String)>,
+}
+
+impl Session<'_> {
+    /// Handles one line of input, either a command or a question
+    /// Returns false once the user asks to exit
+    fn handle_line(&mut self, input: &str) -> bool {
+        // Nothing was asked, so just show the prompt again without scanning the corpus
+        if input.is_empty() {
+            return true;
+        }
+
+        if input.eq_ignore_ascii_case("exit") {
+            outln!("Thank you for using the Thoughtful AI Customer Support Agent. Goodbye!");
+            return false;
+        }
+
+        // A command is its first word, matched exactly so that "/searchable" is not "/search able"
+        let (command, args) = input.split_once(char::is_whitespace).unwrap_or((input, ""));
+        let args = args.trim();
+
+        if command == "/transcript" {
+            let path = args;
+            if path.is_empty() {
+                outln!("Usage: /transcript <path>");
+            } else {
+                match write_transcript(path, &self.history) {
+                    Ok(()) => outln!("Saved {} exchanges to {}", self.history.len(), path),
+                    Err(err) => outln!("Could not save the transcript: {}", err),
+                }
+            }
+            return true;
+        }
+
+        if command == "/search" {
+            let pattern = args;
+            if pattern.is_empty() {
+                outln!("Usage: /search <text>");
+                return true;
+            }
+
+            let matches = search_answers(self.qa_data, self.aliases, pattern);
+            if matches.is_empty() {
+                outln!("No answers contain '{}'", pattern);
+            }
+            for (question, _) in matches {
+                outln!(
+                    "Q: {}\nA: {}",
+                    question,
+                    linked_answer(self.qa_data, question)
+                );
+            }
+            return true;
+        }
+
+        if command == "/contradictions" {
+            let thresholds: Vec<f64> = args
+                .split_whitespace()
+                .filter_map(|arg| arg.parse().ok())
+                .collect();
+            let question_sim_threshold = thresholds.first().copied().unwrap_or(0.7);
+            let answer_sim_threshold_max = thresholds.get(1).copied().unwrap_or(0.3);
+
+            let contradictions = find_contradictions(
+                self.qa_data,
+                self.tfidf_vectors,
+                &self.config.tokenizer,
+                question_sim_threshold,
+                answer_sim_threshold_max,
+            );
+            if contradictions.is_empty() {
+                outln!("No similar questions with differing answers found");
+            }
+            for candidate in contradictions {
+                outln!(
+                    "'{}' / '{}'\n  questions {:.3} similar, answers {:.3} similar",
+                    candidate.q1,
+                    candidate.q2,
+                    display_score(candidate.question_similarity),
+                    display_score(candidate.answer_similarity)
+                );
+            }
+            return true;
+        }
+
+        if command == "/nearest" {
+            let question = args;
+            if question.is_empty() {
+                outln!("Usage: /nearest <question>");
+                return true;
+            }
+            for (other, similarity) in nearest_questions(
+                self.tfidf_vectors,
+                self.idf,
+                &self.config.tokenizer,
+                question,
+                5,
+            ) {
+                outln!("  {:.3}  {}", display_score(similarity), other);
+            }
+            return true;
+        }
+
+        if command == "/related" {
+            let question = args;
+            if question.is_empty() {
+                outln!("Usage: /related <stored question>");
+                return true;
+            }
+            match nearest_to_question(self.tfidf_vectors, question, 5) {
+                Some(related) => {
+                    for (other, similarity) in related {
+                        outln!("  {:.3}  {}", display_score(similarity), other);
+                    }
+                }
+                None => outln!(
+                    "'{}' is not a stored question; use /nearest for free text",
+                    question
+                ),
+            }
+            return true;
+        }
+
+        if command == "/diversity" {
+            outln!(
+                "Answer diversity: {:.3} (1.0 = all answers distinct, 0.0 = all the same)",
+                compute_answer_diversity_score(self.qa_data, self.aliases, &self.config.tokenizer)
+            );
+            return true;
+        }
+
+        if command == "/word" {
+            let word = args;
+            if word.is_empty() {
+                outln!("Usage: /word <word>");
+                return true;
+            }
+            let questions = questions_containing_word(self.qa_data, &self.config.tokenizer, word);
+            if questions.is_empty() {
+                outln!("No question contains '{}'", word);
+            }
+            for question in questions {
+                outln!("  {}", question);
+            }
+            return true;
+        }
+
+        if command == "/suggest" {
+            let prefix = args;
+            if prefix.is_empty() {
+                outln!("Usage: /suggest <prefix>");
+                return true;
+            }
+            let suggestions = suggest(self.qa_data, prefix, 5);
+            if suggestions.is_empty() {
+                outln!("No questions match '{}'", prefix);
+            }
+            for question in suggestions {
+                outln!("  {}", question);
+            }
+            return true;
+        }
+
+        if command == "/vocab-coverage" {
+            let vocabulary: HashSet<String> =
+                tokenize(args, &self.config.tokenizer).into_iter().collect();
+            if vocabulary.is_empty() {
+                outln!("Usage: /vocab-coverage <term> [term...]");
+                return true;
+            }
+            outln!(
+                "{:.1}% of {} terms appear in the questions",
+                compute_query_coverage(self.idf, &vocabulary) * 100.0,
+                vocabulary.len()
+            );
+            return true;
+        }
+
+        if command == "/gaps" {
+            let terms: Vec<&str> = args.split_whitespace().collect();
+            if terms.is_empty() {
+                outln!("Usage: /gaps <term> [term...]");
+                return true;
+            }
+            let gaps = find_answer_gaps(self.idf, &self.config.tokenizer, &terms);
+            if gaps.is_empty() {
+                outln!("Every term appears in at least one question");
+            }
+            for term in gaps {
+                outln!("  {}", term);
+            }
+            return true;
+        }
+
+        if command == "/why" {
+            let Some([query, question]) =
+                parse_quoted_args(args).and_then(|args| <[String; 2]>::try_from(args).ok())
+            else {
+                outln!("Usage: /why \"<query>\" \"<stored question>\"");
+                return true;
+            };
+            match explain_match(self.tfidf_vectors, self.idf, self.config, &query, &question) {
+                Some(explanation) => {
+                    // A query of only unknown words has no defined similarity
+                    let similarity = if explanation.similarity.is_nan() {
+                        0.0
+                    } else {
+                        explanation.similarity
+                    };
+                    outln!(
+                        "Similarity {:.3} (threshold {})",
+                        display_score(similarity),
+                        self.config.threshold
+                    );
+                    outln!("  shared:           {}", explanation.shared.join(" "));
+                    outln!("  only in query:    {}", explanation.query_only.join(" "));
+                    outln!(
+                        "  only in question: {}",
+                        explanation.question_only.join(" ")
+                    );
+                }
+                None => outln!("'{}' is not a stored question", question),
+            }
+            return true;
+        }
+
+        if command == "/weights" {
+            let question = args;
+            if question.is_empty() {
+                outln!("Usage: /weights <question>");
+                return true;
+            }
+            for (term, weight) in compute_term_importance(self.idf, self.config, question) {
+                outln!("  {:<30} {:.4}", term, weight);
+            }
+            return true;
+        }
+
+        if command == "/improve" {
+            let question = args;
+            if question.is_empty() {
+                outln!("Usage: /improve <question>");
+                return true;
+            }
+            let variants =
+                suggest_question_improvements(self.idf, &self.config.tokenizer, question);
+            if variants.is_empty() {
+                outln!("No common words to replace in '{}'", question);
+            }
+            for variant in variants {
+                outln!("  {}", variant);
+            }
+            return true;
+        }
+
+        if command == "/thin" {
+            let threshold = if args.is_empty() {
+                0.1
+            } else {
+                match args.parse::<f64>() {
+                    Ok(threshold) => threshold,
+                    Err(_) => {
+                        outln!("Usage: /thin [fraction]");
+                        return true;
+                    }
+                }
+            };
+            let terms = find_low_coverage_terms(self.tfidf_vectors, threshold);
+            if terms.is_empty() {
+                outln!(
+                    "Every term appears in at least {:.1}% of the questions",
+                    threshold * 100.0
+                );
+            }
+            for (term, count) in terms {
+                outln!("  {:<30} {} of {}", term, count, self.tfidf_vectors.len());
+            }
+            return true;
+        }
+
+        if command == "/stats" {
+            let summary =
+                summarize_corpus(self.qa_data, self.aliases, self.idf, &self.config.tokenizer);
+            outln!("Q&A pairs:             {}", summary.total_pairs);
+            outln!("Vocabulary size:       {}", summary.vocabulary_size);
+            outln!("Avg. question tokens:  {:.1}", summary.avg_question_tokens);
+            outln!(
+                "Avg. answer length:    {:.1} characters",
+                summary.avg_answer_chars
+            );
+            let format_percentiles = |values: &[(usize, usize)]| {
+                values
+                    .iter()
+                    .map(|(p, value)| format!("p{} {}", p, value))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+            outln!(
+                "Question tokens:       {}",
+                format_percentiles(&summary.question_token_percentiles)
+            );
+            outln!(
+                "Answer characters:     {}",
+                format_percentiles(&summary.answer_char_percentiles)
+            );
+            let top_terms: Vec<&str> = summary.top_terms.iter().map(|(t, _)| t.as_str()).collect();
+            outln!("Most common terms:     {}", top_terms.join(" "));
+            return true;
+        }
+
+        if command == "/unmatchable" {
+            let unmatchable =
+                find_unmatchable(self.tfidf_vectors, self.idf, self.config.idf_log_base);
+            if unmatchable.is_empty() {
+                outln!("Every question has at least one discriminating word");
+            }
+            for (question, reason) in unmatchable {
+                outln!("{}\n  {}", question, reason);
+            }
+            return true;
+        }
+
+        let response = self.answer_query(input).answer;
+        outln!("{}", response);
+        // Only the transcript copy is redacted; the answer above was computed from the original text
+        self.history.push((redact(input, self.config), response));
+        true
+    }
+
+    /// Answers a query, also reporting which question matched and how well
+    /// The corpus is scored once, and the same candidates give both the answer and the match
+    fn answer_query<'b>(&'b self, query: &'b str) -> QueryResult<'b> {
+        let mut candidates = score_candidates(
+            self.tfidf_vectors,
+            self.idf,
+            self.phonetic_index,
+            self.answer_index,
+            query,
+            self.config,
+        );
+        let best = best_candidate(self.qa_data, &candidates, self.config);
+        QueryResult {
+            query,
+            answer: get_response(
+                self.qa_data,
+                self.aliases,
+                self.idf,
+                query,
+                &mut candidates,
+                best,
+                self.config,
+            ),
+            matched_question: best
+                .filter(|(_, similarity)| *similarity > self.config.threshold)
+                .map(|(question, _)| primary_question(self.aliases, question)),
+            score: best.map(|(_, similarity)| display_score(similarity)),
         }
     }
+}
+
+/// Returns the Q&A pairs whose answer contains `pattern` (case-insensitive), sorted by question
+/// This is a plain linear scan for admin lookups, not a ranked retrieval; aliases are left out, since they
+/// share their question's answer
+fn search_answers<'a>(
+    qa_data: &'a HashMap<String, String>,
+    aliases: &HashMap<String, String>,
+    pattern: &str,
+) -> Vec<(&'a String, &'a String)> {
+    let pattern = pattern.to_lowercase();
+    let mut matches: Vec<(&String, &String)> = qa_data
+        .iter()
+        .filter(|(question, _)| !aliases.contains_key(*question))
+        .filter(|(_, answer)| answer.to_lowercase().contains(&pattern))
+        .collect();
+    matches.sort();
+    matches
+}
 
-    if max_similarity > 0.5 {
-        qa_data.get(&best_match).unwrap().clone()
+/// Replaces every match of the `--redact` patterns in a query with the placeholder, e.g. to keep email
+/// addresses and phone numbers out of saved transcripts
+fn redact(input: &str, config: &Config) -> String {
+    config
+        .redact_patterns
+        .iter()
+        .fold(input.to_string(), |text, pattern| {
+            pattern
+                .replace_all(&text, regex::NoExpand(&config.redact_placeholder))
+                .into_owned()
+        })
+}
+
+/// Writes the session history to `path`, as a JSON array for `.json` paths and as plain text otherwise
+fn write_transcript(
+    path: &str,
+    history: &[(String, String)],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut writer = BufWriter::new(File::create(path)?);
+
+    if path.ends_with(".json") {
+        let turns: Vec<Value> = history
+            .iter()
+            .map(|(question, response)| json!({ "question": question, "response": response }))
+            .collect();
+        serde_json::to_writer_pretty(&mut writer, &turns)?;
+        writeln!(writer)?;
     } else {
-        if best_match.is_empty() {
-            return "I'm sorry, I couldn't find a relevant question. Please try rephrasing your question.".to_string();
-        } else {
-            format!("I'm sorry, I don't have specific information about that. The closest question I can answer is: '{}'. Would you like me to answer that instead?", best_match)
+        for (question, response) in history {
+            writeln!(writer, "Q: {}\nA: {}\n", question, response)?;
         }
     }
+
+    writer.flush()?;
+    Ok(())
 }
 
-/// Computes the TF-IDF vector for the input question
-fn compute_input_vector(input: &str, idf: &HashMap<String, f64>) -> HashMap<String, f64> {
-    let words: Vec<String> = input
-        .to_lowercase()
-        .split_whitespace()
-        .map(String::from)
-        .collect();
-    let mut tf: HashMap<String, usize> = HashMap::new();
-    for word in &words {
-        *tf.entry(word.clone()).or_insert(0) += 1;
+/// Initializes QA data by reading from a JSON (or JSON-Lines, for `.jsonl` paths) file and parsing it into a HashMap
+/// With `strict`, an entry carrying a field other than "question", "answer", "aliases" or an `_`-prefixed annotation
+/// is an error rather than being ignored, to catch typos such as "anser"; so is a top-level key other than
+/// "questions" or an `_`-prefixed annotation. A JSON file without a "questions" array is always an error
+/// Also returns the TF-IDF index stored in a file written by `--export-vectors`, if any
+fn initialize_qa_data(
+    path: &str,
+    retry_policy: &RetryPolicy,
+    strict: bool,
+) -> Result<(Corpus, Option<TfidfIndex>), Box<dyn std::error::Error>> {
+    // Open the file
+    let mut file = open_with_retry(path, retry_policy)?;
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes)?;
+
+    // Only UTF-8 is supported; a byte order mark, as written by some Windows tools, is skipped
+    let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(&bytes);
+    let text = std::str::from_utf8(bytes).map_err(|err| {
+        format!(
+            "not valid UTF-8 ({}); re-save the file as UTF-8, the only supported encoding",
+            err
+        )
+    })?;
+
+    if path.ends_with(".jsonl") {
+        return from_jsonl_stream(text.as_bytes(), strict);
     }
 
-    let mut tfidf = HashMap::new();
-    for (word, count) in tf {
-        let tf = count as f64 / words.len() as f64;
-        let idf_value = idf.get(&word).unwrap_or(&0.0);
-        tfidf.insert(word, tf * idf_value);
+    // Parse the JSON
+    let json: Value = serde_json::from_str(text)?;
+
+    // Create a HashMap to store the Q&A pairs
+    let mut qa_data = HashMap::new();
+    let mut entry_aliases = Vec::new();
+
+    if strict {
+        check_top_level_fields(&json)?;
     }
-    tfidf
+
+    // Extract the questions and answers from the JSON
+    let questions = json["questions"]
+        .as_array()
+        .ok_or("expected a top-level \"questions\" array")?;
+    for (index, q) in questions.iter().enumerate() {
+        if strict {
+            check_fields(q, false).map_err(|err| format!("entry {}: {}", index + 1, err))?;
+        }
+        if let (Some(question), Some(answer)) = (q["question"].as_str(), q["answer"].as_str()) {
+            qa_data.insert(question.to_string(), answer.to_string());
+            entry_aliases.extend(read_aliases(q, question));
+        }
+    }
+
+    let aliases = insert_aliases(&mut qa_data, entry_aliases)?;
+    Ok(((qa_data, aliases), None))
 }
 
-/// Calculates the cosine similarity between two TF-IDF vectors
-fn cosine_similarity(v1: &HashMap<String, f64>, v2: &HashMap<String, f64>) -> f64 {
-    let mut dot_product = 0.0;
-    let mut mag1 = 0.0;
-    let mut mag2 = 0.0;
+/// Returns an entry's `"aliases"` (alternative phrasings), each paired with the entry's question
+fn read_aliases(entry: &Value, question: &str) -> Vec<(String, String)> {
+    entry["aliases"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .map(|alias| (alias.to_string(), question.to_string()))
+        .collect()
+}
 
-    for (word, value) in v1 {
-        dot_product += value * v2.get(word).unwrap_or(&0.0);
-        mag1 += value * value;
+/// Indexes each (alias, question) pair's alias as another question with that question's answer, and returns
+/// the map from alias to question
+/// This runs once every question is loaded, so an alias that is another entry's question, or an alias of
+/// another question, is rejected wherever it appears in the file instead of silently replacing that entry
+fn insert_aliases(
+    qa_data: &mut HashMap<String, String>,
+    entry_aliases: Vec<(String, String)>,
+) -> Result<HashMap<String, String>, String> {
+    let mut aliases: HashMap<String, String> = HashMap::new();
+    for (alias, question) in entry_aliases {
+        // Repeating the question itself, or one of its aliases, adds nothing
+        if alias == question || aliases.get(&alias) == Some(&question) {
+            continue;
+        }
+        if let Some(other) = aliases.get(&alias) {
+            return Err(format!(
+                "alias '{}' of '{}' is already an alias of '{}'",
+                alias, question, other
+            ));
+        }
+        if qa_data.contains_key(&alias) {
+            return Err(format!(
+                "alias '{}' of '{}' is already a question",
+                alias, question
+            ));
+        }
+        let answer = qa_data[&question].clone();
+        qa_data.insert(alias.clone(), answer);
+        aliases.insert(alias, question);
     }
+    Ok(aliases)
+}
 
-    for value in v2.values() {
-        mag2 += value * value;
+/// Returns the question `question` is an alias of, or `question` itself if it isn't an alias
+fn primary_question<'a>(aliases: &'a HashMap<String, String>, question: &'a String) -> &'a String {
+    aliases.get(question).unwrap_or(question)
+}
+
+/// Returns the aliases of `question`, sorted
+fn aliases_of<'a>(aliases: &'a HashMap<String, String>, question: &str) -> Vec<&'a String> {
+    let mut found: Vec<&String> = aliases
+        .iter()
+        .filter(|(_, primary)| primary.as_str() == question)
+        .map(|(alias, _)| alias)
+        .collect();
+    found.sort();
+    found
+}
+
+/// Checks that a Q&A entry only has the fields the loader knows (including "aliases"), plus `_`-prefixed annotations such as "_comment"
+/// `with_vectors` also allows the "vector" and "alias_vectors" fields written by `to_jsonl_stream_with_vectors`
+fn check_fields(entry: &Value, with_vectors: bool) -> Result<(), String> {
+    let Some(fields) = entry.as_object() else {
+        return Err("expected an object".to_string());
+    };
+    match fields.keys().find(|key| {
+        let vector_field = with_vectors && matches!(key.as_str(), "vector" | "alias_vectors");
+        !matches!(key.as_str(), "question" | "answer" | "aliases")
+            && !vector_field
+            && !key.starts_with('_')
+    }) {
+        Some(key) => Err(format!(
+            "unknown field '{}' (expected \"question\", \"answer\" and optional \"aliases\")",
+            key
+        )),
+        None => Ok(()),
     }
+}
 
-    dot_product / (mag1.sqrt() * mag2.sqrt())
+/// Checks that a JSON data file only has a "questions" key at the top level, plus `_`-prefixed annotations
+fn check_top_level_fields(json: &Value) -> Result<(), String> {
+    let Some(fields) = json.as_object() else {
+        return Err("expected an object with a \"questions\" array".to_string());
+    };
+    match fields
+        .keys()
+        .find(|key| key.as_str() != "questions" && !key.starts_with('_'))
+    {
+        Some(key) => Err(format!(
+            "unknown top-level field '{}' (expected \"questions\")",
+            key
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Opens a file, retrying with exponential backoff on I/O errors such as the file being locked mid-write
+fn open_with_retry(path: &str, retry_policy: &RetryPolicy) -> io::Result<File> {
+    with_retry(path, retry_policy, || File::open(path))
+}
+
+/// Runs `operation` on `path` until it succeeds, waiting between attempts as `retry_policy` says
+fn with_retry<T, F>(path: &str, retry_policy: &RetryPolicy, mut operation: F) -> io::Result<T>
+where
+    F: FnMut() -> io::Result<T>,
+{
+    let mut attempt = 1;
+    loop {
+        match operation() {
+            Ok(value) => return Ok(value),
+            // A missing file will not appear by waiting, so fail straight away
+            Err(err)
+                if attempt < retry_policy.max_attempts && err.kind() != io::ErrorKind::NotFound =>
+            {
+                let delay = retry_policy.delay_ms(attempt);
+                eprintln!(
+                    "Could not open {} ({}), retrying in {} ms",
+                    path, err, delay
+                );
+                thread::sleep(Duration::from_millis(delay));
+                attempt += 1;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Writes an export file with `write`, exiting with an error if the file cannot be created or written
+fn export_or_exit<F>(path: &str, write: F)
+where
+    F: FnOnce(&mut BufWriter<File>) -> Result<(), Box<dyn std::error::Error>>,
+{
+    let export = || -> Result<(), Box<dyn std::error::Error>> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        write(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    };
+    if let Err(err) = export() {
+        eprintln!("Error: could not write {}: {}", path, err);
+        process::exit(1);
+    }
+}
+
+/// Writes every Q&A pair as one JSON object per line, ordered by question so the output is stable
+/// Aliases are listed in their question's "aliases" field rather than written as pairs of their own
+fn to_jsonl_stream<W: Write>(
+    qa_data: &HashMap<String, String>,
+    aliases: &HashMap<String, String>,
+    writer: &mut W,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut questions: Vec<&String> = qa_data
+        .keys()
+        .filter(|question| !aliases.contains_key(*question))
+        .collect();
+    questions.sort();
+
+    for question in questions {
+        let mut line = json!({ "question": question, "answer": qa_data[question] });
+        let question_aliases = aliases_of(aliases, question);
+        if !question_aliases.is_empty() {
+            line["aliases"] = json!(question_aliases);
+        }
+        writeln!(writer, "{}", line)?;
+    }
+
+    Ok(())
+}
+
+/// Writes Q&A pairs as JSON-Lines like `to_jsonl_stream`, adding each question's TF-IDF vector for offline use
+/// The IDF table comes first, on a line of its own as `{"idf": {...}}`, and each alias's vector is listed in its
+/// question's "alias_vectors", so `from_jsonl_stream` can load the whole index back instead of recomputing it
+fn to_jsonl_stream_with_vectors<W: Write>(
+    qa_data: &HashMap<String, String>,
+    aliases: &HashMap<String, String>,
+    tfidf_vectors: &HashMap<String, HashMap<String, f64>>,
+    idf: &HashMap<String, f64>,
+    writer: &mut W,
+) -> Result<(), Box<dyn std::error::Error>> {
+    writeln!(writer, "{}", json!({ "idf": idf }))?;
+
+    let mut questions: Vec<&String> = qa_data
+        .keys()
+        .filter(|question| !aliases.contains_key(*question))
+        .collect();
+    questions.sort();
+
+    for question in questions {
+        let mut line = json!({
+            "question": question,
+            "answer": qa_data[question],
+            "vector": tfidf_vectors[question],
+        });
+        let question_aliases = aliases_of(aliases, question);
+        if !question_aliases.is_empty() {
+            let alias_vectors: HashMap<&String, &HashMap<String, f64>> = question_aliases
+                .iter()
+                .map(|alias| (*alias, &tfidf_vectors[*alias]))
+                .collect();
+            line["aliases"] = json!(question_aliases);
+            line["alias_vectors"] = json!(alias_vectors);
+        }
+        writeln!(writer, "{}", line)?;
+    }
+
+    Ok(())
+}
+
+/// Reads Q&A pairs written by `to_jsonl_stream`, one JSON object per line; blank lines are skipped
+/// With `strict`, unknown fields are rejected as in `initialize_qa_data`
+/// A file written by `to_jsonl_stream_with_vectors` also yields its TF-IDF index, which must then have a vector
+/// for every question and alias; without the "idf" line, "vector" fields are ignored
+fn from_jsonl_stream<R: BufRead>(
+    reader: R,
+    strict: bool,
+) -> Result<(Corpus, Option<TfidfIndex>), Box<dyn std::error::Error>> {
+    let mut qa_data = HashMap::new();
+    let mut entry_aliases = Vec::new();
+    let mut idf: Option<HashMap<String, f64>> = None;
+    let mut tfidf_vectors: HashMap<String, HashMap<String, f64>> = HashMap::new();
+
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let entry: Value = serde_json::from_str(&line)?;
+        if let Some(table) = entry.get("idf") {
+            if idf.is_some() {
+                return Err(format!("line {}: the \"idf\" table is repeated", index + 1).into());
+            }
+            idf = Some(
+                read_weights(table)
+                    .ok_or_else(|| format!("line {}: invalid \"idf\" table", index + 1))?,
+            );
+            continue;
+        }
+        if strict {
+            check_fields(&entry, true).map_err(|err| format!("line {}: {}", index + 1, err))?;
+        }
+        match (entry["question"].as_str(), entry["answer"].as_str()) {
+            (Some(question), Some(answer)) => {
+                qa_data.insert(question.to_string(), answer.to_string());
+                entry_aliases.extend(read_aliases(&entry, question));
+
+                let invalid = || format!("line {}: invalid \"vector\"", index + 1);
+                if let Some(vector) = entry.get("vector") {
+                    tfidf_vectors.insert(
+                        question.to_string(),
+                        read_weights(vector).ok_or_else(invalid)?,
+                    );
+                }
+                if let Some(alias_vectors) = entry.get("alias_vectors") {
+                    for (alias, vector) in alias_vectors.as_object().ok_or_else(invalid)? {
+                        tfidf_vectors
+                            .insert(alias.clone(), read_weights(vector).ok_or_else(invalid)?);
+                    }
+                }
+            }
+            _ => {
+                return Err(format!(
+                    "line {}: expected \"question\" and \"answer\" strings",
+                    index + 1
+                )
+                .into())
+            }
+        }
+    }
+
+    let aliases = insert_aliases(&mut qa_data, entry_aliases)?;
+
+    let index = match idf {
+        Some(idf) => {
+            let mut missing: Vec<&String> = qa_data
+                .keys()
+                .filter(|question| !tfidf_vectors.contains_key(*question))
+                .collect();
+            missing.sort();
+            if let Some(question) = missing.first() {
+                return Err(format!(
+                    "'{}' has no \"vector\", though the file has an \"idf\" table",
+                    question
+                )
+                .into());
+            }
+            tfidf_vectors.retain(|question, _| qa_data.contains_key(question));
+            Some((tfidf_vectors, idf))
+        }
+        None => None,
+    };
+    Ok(((qa_data, aliases), index))
+}
+
+/// Reads a JSON object of numbers, such as a TF-IDF vector or the IDF table, or None if it is anything else
+fn read_weights(value: &Value) -> Option<HashMap<String, f64>> {
+    value
+        .as_object()?
+        .iter()
+        .map(|(word, weight)| Some((word.clone(), weight.as_f64()?)))
+        .collect()
+}
+
+/// Result of collapsing questions that share the same answer
+struct DeduplicationReport {
+    removed_count: usize,
+    /// Each group lists the canonical (kept) question first, followed by the removed ones
+    groups: Vec<Vec<String>>,
+}
+
+/// Result of splitting over-long questions into one entry per sentence
+struct SplitReport {
+    split_count: usize,
+    /// The (question, answer) entries created from the fragments
+    new_entries: Vec<(String, String)>,
+}
+
+/// Replaces every question longer than `max_tokens` tokens with one entry per sentence (split at `.`, `?` and `!`),
+/// each keeping the original answer, since a long question dilutes the term frequency of each of its words
+/// Questions made of a single sentence are left alone; fragments that are already questions keep their own answer
+/// Aliases are not split; those of a split question become aliases of its first new entry, or questions of their
+/// own if it has none
+fn split_long_questions(
+    qa_data: &mut HashMap<String, String>,
+    aliases: &mut HashMap<String, String>,
+    options: &TokenizerOptions,
+    max_tokens: usize,
+) -> SplitReport {
+    let mut long_questions: Vec<String> = qa_data
+        .keys()
+        .filter(|question| !aliases.contains_key(*question))
+        .filter(|question| tokenize(question, options).len() > max_tokens)
+        .cloned()
+        .collect();
+    long_questions.sort();
+
+    let mut report = SplitReport {
+        split_count: 0,
+        new_entries: Vec::new(),
+    };
+    for question in long_questions {
+        let fragments: Vec<String> = question
+            .split_inclusive(['.', '?', '!'])
+            .map(|fragment| fragment.trim().to_string())
+            .filter(|fragment| fragment.chars().any(char::is_alphanumeric))
+            .collect();
+        if fragments.len() < 2 {
+            continue;
+        }
+
+        let answer = qa_data.remove(&question).unwrap_or_default();
+        report.split_count += 1;
+        let first_new_entry = report.new_entries.len();
+        for fragment in fragments {
+            if !qa_data.contains_key(&fragment) {
+                qa_data.insert(fragment.clone(), answer.clone());
+                report.new_entries.push((fragment, answer.clone()));
+            }
+        }
+
+        match report.new_entries.get(first_new_entry) {
+            Some((primary, _)) => {
+                for target in aliases.values_mut().filter(|target| **target == question) {
+                    *target = primary.clone();
+                }
+            }
+            None => aliases.retain(|_, target| *target != question),
+        }
+    }
+    report
+}
+
+/// Tidies answer text pasted from spreadsheets: dash variants become a plain hyphen, runs of whitespace
+/// (including line breaks) collapse to one space and the ends are trimmed
+fn normalize_answer(answer: &str) -> String {
+    answer
+        .replace(
+            [
+                '\u{2010}', '\u{2011}', '\u{2012}', '\u{2013}', '\u{2014}', '\u{2015}', '\u{2212}',
+            ],
+            "-",
+        )
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Groups questions that are different strings but tokenize to the same set of words, ignoring punctuation at
+/// word edges, like "What is EVA?" and "what is eva"; they split one entry in two and tie on every query
+/// Each group is sorted, and groups are ordered by their first question
+/// A group made only of one question and its aliases is not reported, since those already share an answer
+fn find_normalized_duplicates(
+    qa_data: &HashMap<String, String>,
+    aliases: &HashMap<String, String>,
+    options: &TokenizerOptions,
+) -> Vec<Vec<String>> {
+    let mut by_tokens: HashMap<Vec<String>, Vec<String>> = HashMap::new();
+    for question in qa_data.keys() {
+        let mut tokens: Vec<String> = tokenize(question, options)
+            .iter()
+            .map(|word| {
+                word.trim_matches(|c: char| !c.is_alphanumeric())
+                    .to_string()
+            })
+            .filter(|word| !word.is_empty())
+            .collect();
+        tokens.sort();
+        tokens.dedup();
+        by_tokens.entry(tokens).or_default().push(question.clone());
+    }
+
+    let mut groups: Vec<Vec<String>> = by_tokens
+        .into_values()
+        .filter(|group| {
+            let primary = primary_question(aliases, &group[0]);
+            group
+                .iter()
+                .any(|question| primary_question(aliases, question) != primary)
+        })
+        .map(|mut group| {
+            group.sort();
+            group
+        })
+        .collect();
+    groups.sort();
+    groups
+}
+
+/// Removes questions whose answers are identical after normalization, keeping the shortest question per answer
+/// Aliases are not questions of their own here; those of a removed question become aliases of the kept one
+fn deduplicate_answers(
+    qa_data: &mut HashMap<String, String>,
+    aliases: &mut HashMap<String, String>,
+) -> DeduplicationReport {
+    // Group questions by their answer, ignoring case and whitespace differences
+    let mut by_answer: HashMap<String, Vec<String>> = HashMap::new();
+    for (question, answer) in qa_data
+        .iter()
+        .filter(|(question, _)| !aliases.contains_key(*question))
+    {
+        let normalized = answer
+            .to_lowercase()
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ");
+        by_answer
+            .entry(normalized)
+            .or_default()
+            .push(question.clone());
+    }
+
+    let mut groups: Vec<Vec<String>> = by_answer
+        .into_values()
+        .filter(|questions| questions.len() > 1)
+        .collect();
+
+    let mut removed_count = 0;
+    for group in &mut groups {
+        group.sort_by(|a, b| a.len().cmp(&b.len()).then_with(|| a.cmp(b)));
+        for question in &group[1..] {
+            qa_data.remove(question);
+            removed_count += 1;
+        }
+        for primary in aliases.values_mut() {
+            if group[1..].contains(primary) {
+                *primary = group[0].clone();
+            }
+        }
+    }
+    groups.sort();
+
+    DeduplicationReport {
+        removed_count,
+        groups,
+    }
+}
+
+/// Converts natural-log IDF values, and the vectors built from them, to logarithms in `base`
+/// Every weight is divided by the same ln(base), so similarities and rankings are unchanged; only the reported
+/// values change, to line up with tools that use log base 2 or 10
+fn change_idf_log_base(
+    tfidf_vectors: &mut HashMap<String, HashMap<String, f64>>,
+    idf: &mut HashMap<String, f64>,
+    base: f64,
+) {
+    let ln_base = base.ln();
+    for value in idf.values_mut().chain(
+        tfidf_vectors
+            .values_mut()
+            .flat_map(|vector| vector.values_mut()),
+    ) {
+        *value /= ln_base;
+    }
+}
+
+/// Builds the phonetic and answer indexes, each only when `config` blends its signal into the score
+/// `--max-vocab` caps them like the question index, though without a warning of its own
+fn compute_secondary_indexes(
+    qa_data: &HashMap<String, String>,
+    config: &Config,
+) -> (Option<TfidfIndex>, Option<TfidfIndex>) {
+    let build = |field, tokenizer| {
+        let (mut tfidf_vectors, mut idf) = compute_tfidf(
+            qa_data,
+            field,
+            tokenizer,
+            &config.tokenizer,
+            &HashMap::new(),
+        );
+        if let Some(max_terms) = config.max_vocabulary_size {
+            truncate_vocabulary(
+                &mut tfidf_vectors,
+                &mut idf,
+                max_terms,
+                config.vocabulary_rank,
+            );
+        }
+        (tfidf_vectors, idf)
+    };
+    let phonetic_index =
+        (config.phonetic_weight > 0.0).then(|| build(Field::Question, phonetic_tokenize));
+    let answer_index = config.index_answers.then(|| build(Field::Answer, tokenize));
+    (phonetic_index, answer_index)
+}
+
+/// Keeps only the top `max_terms` terms by `rank`, removing the rest from the IDF table and every vector
+/// Queries are vectorized against the same IDF table, so dropped terms carry no weight in queries either
+/// Returns how many terms were dropped
+fn truncate_vocabulary(
+    tfidf_vectors: &mut HashMap<String, HashMap<String, f64>>,
+    idf: &mut HashMap<String, f64>,
+    max_terms: usize,
+    rank: VocabularyRank,
+) -> usize {
+    if idf.len() <= max_terms {
+        return 0;
+    }
+
+    let mut terms: Vec<(String, f64)> = idf.drain().collect();
+    // IDF falls as document frequency rises, so ranking by frequency is ranking by ascending IDF
+    terms.sort_by(|a, b| {
+        let by_idf = match rank {
+            VocabularyRank::Idf => b.1.total_cmp(&a.1),
+            VocabularyRank::DocumentFrequency => a.1.total_cmp(&b.1),
+        };
+        by_idf.then_with(|| a.0.cmp(&b.0))
+    });
+    let dropped = terms.len() - max_terms;
+    idf.extend(terms.into_iter().take(max_terms));
+
+    for vector in tfidf_vectors.values_mut() {
+        vector.retain(|word, _| idf.contains_key(word));
+    }
+    dropped
+}
+
+/// Which text of each Q&A pair is indexed
+#[derive(Clone, Copy)]
+enum Field {
+    Question,
+    Answer,
+}
+
+/// Computes TF-IDF vectors for all questions in the QA data, using `tokenizer` to split the chosen field
+/// Vectors are keyed by question whichever field is indexed
+/// Weights of terms listed in `term_boost` are multiplied by their boost factor
+/// Returns a tuple containing:
+/// 1. A HashMap of TF-IDF vectors for each question
+/// 2. The IDF (Inverse Document Frequency) scores for all words
+fn compute_tfidf(
+    qa_data: &HashMap<String, String>,
+    field: Field,
+    tokenizer: Tokenizer,
+    options: &TokenizerOptions,
+    term_boost: &HashMap<String, f64>,
+) -> TfidfIndex {
+    let mut word_doc_count: HashMap<String, usize> = HashMap::new();
+    let mut tfidf_vectors: HashMap<String, HashMap<String, f64>> = HashMap::new();
+    let text = |question: &String| match field {
+        Field::Question => question.clone(),
+        Field::Answer => qa_data[question].clone(),
+    };
+
+    // Compute document frequency
+    for question in qa_data.keys() {
+        let words: HashSet<String> = tokenizer(&text(question), options).into_iter().collect();
+        for word in words {
+            *word_doc_count.entry(word).or_insert(0) += 1;
+        }
+    }
+
+    // Compute IDF
+    // With a single question every word would get ln(1/1) = 0 and nothing could ever match,
+    // so every word gets a flat IDF of 1.0 instead
+    let doc_count = qa_data.len() as f64;
+    let idf: HashMap<String, f64> = word_doc_count
+        .iter()
+        .map(|(word, count)| {
+            let idf_value = if qa_data.len() == 1 {
+                1.0
+            } else {
+                (doc_count / *count as f64).ln()
+            };
+            (word.clone(), idf_value)
+        })
+        .collect();
+
+    // Compute TF-IDF
+    for question in qa_data.keys() {
+        let mut tf: HashMap<String, usize> = HashMap::new();
+        let words = tokenizer(&text(question), options);
+        for word in &words {
+            *tf.entry(word.clone()).or_insert(0) += 1;
+        }
+
+        let mut tfidf = HashMap::new();
+        for (word, count) in tf {
+            let tf = count as f64 / words.len() as f64;
+            let idf_value = idf.get(&word).unwrap_or(&0.0);
+            let boost = term_boost.get(&word).unwrap_or(&1.0);
+            tfidf.insert(word, tf * idf_value * boost);
+        }
+        tfidf_vectors.insert(question.clone(), tfidf);
+    }
+
+    (tfidf_vectors, idf)
+}
+
+/// Prints the `top_n` rarest (highest IDF) and most common (lowest IDF) terms, to help tune retrieval
+fn print_idf_table(idf: &HashMap<String, f64>, top_n: usize) {
+    let mut terms: Vec<(&String, &f64)> = idf.iter().collect();
+    // Sort by IDF descending, breaking ties alphabetically so the output is stable
+    terms.sort_by(|a, b| b.1.total_cmp(a.1).then_with(|| a.0.cmp(b.0)));
+
+    outln!("Highest IDF (rarest terms):");
+    for (term, value) in terms.iter().take(top_n) {
+        outln!("  {:<30} {:>8.4}", term, value);
+    }
+
+    outln!("Lowest IDF (most common terms):");
+    for (term, value) in terms.iter().rev().take(top_n) {
+        outln!("  {:<30} {:>8.4}", term, value);
+    }
+}
+
+/// Finds questions that can hardly ever be the unique best match, with the reason for each
+/// A question is flagged when all of its words are (nearly) as common as possible, or when another
+/// question weighs at least as much on every one of its words
+/// `idf_log_base` is the base the IDF values were converted to, so the threshold is scaled to match
+fn find_unmatchable(
+    tfidf_vectors: &HashMap<String, HashMap<String, f64>>,
+    idf: &HashMap<String, f64>,
+    idf_log_base: f64,
+) -> Vec<(String, String)> {
+    // Near-zero in natural-log units
+    const NEAR_ZERO_IDF: f64 = 0.05;
+    let near_zero_idf = NEAR_ZERO_IDF / idf_log_base.ln();
+    let mut unmatchable = Vec::new();
+
+    for (question, vector) in tfidf_vectors {
+        if vector.is_empty() {
+            continue;
+        }
+
+        if vector.keys().all(|word| idf[word] < near_zero_idf) {
+            unmatchable.push((
+                question.clone(),
+                "all of its words appear in almost every question".to_string(),
+            ));
+            continue;
+        }
+
+        let dominated_by = tfidf_vectors.iter().find(|(other, other_vector)| {
+            *other != question
+                && vector
+                    .iter()
+                    .all(|(word, weight)| other_vector.get(word).unwrap_or(&0.0) >= weight)
+        });
+        if let Some((other, _)) = dominated_by {
+            unmatchable.push((
+                question.clone(),
+                format!(
+                    "'{}' weighs at least as much on every one of its words",
+                    other
+                ),
+            ));
+        }
+    }
+
+    unmatchable.sort();
+    unmatchable
+}
+
+/// Two questions that look alike but have very different answers, which may contradict each other
+struct ContradictionCandidate {
+    q1: String,
+    q2: String,
+    question_similarity: f64,
+    answer_similarity: f64,
+}
+
+/// Finds question pairs at least `question_sim_threshold` similar whose answers are at most
+/// `answer_sim_threshold_max` similar, most similar questions first
+fn find_contradictions(
+    qa_data: &HashMap<String, String>,
+    tfidf_vectors: &HashMap<String, HashMap<String, f64>>,
+    options: &TokenizerOptions,
+    question_sim_threshold: f64,
+    answer_sim_threshold_max: f64,
+) -> Vec<ContradictionCandidate> {
+    let (answer_vectors, _) =
+        compute_tfidf(qa_data, Field::Answer, tokenize, options, &HashMap::new());
+
+    let mut questions: Vec<&String> = tfidf_vectors.keys().collect();
+    questions.sort();
+
+    let mut contradictions = Vec::new();
+    for i in 0..questions.len() {
+        for j in i + 1..questions.len() {
+            let (q1, q2) = (questions[i], questions[j]);
+            let question_similarity = cosine_similarity(&tfidf_vectors[q1], &tfidf_vectors[q2]);
+            if question_similarity.is_nan() || question_similarity < question_sim_threshold {
+                continue;
+            }
+
+            // Answers without any weighted words count as entirely dissimilar
+            let answer_similarity = cosine_similarity(&answer_vectors[q1], &answer_vectors[q2]);
+            let answer_similarity = if answer_similarity.is_nan() {
+                0.0
+            } else {
+                answer_similarity
+            };
+            if answer_similarity <= answer_sim_threshold_max {
+                contradictions.push(ContradictionCandidate {
+                    q1: q1.clone(),
+                    q2: q2.clone(),
+                    question_similarity,
+                    answer_similarity,
+                });
+            }
+        }
+    }
+
+    contradictions.sort_by(|a, b| b.question_similarity.total_cmp(&a.question_similarity));
+    contradictions
+}
+
+/// Returns the `n` indexed questions most similar to `question`, excluding the question itself
+/// Useful to spot near-duplicates before adding an entry
+fn nearest_questions(
+    tfidf_vectors: &HashMap<String, HashMap<String, f64>>,
+    idf: &HashMap<String, f64>,
+    options: &TokenizerOptions,
+    question: &str,
+    n: usize,
+) -> Vec<(String, f64)> {
+    let query_vector = compute_input_vector(question, idf, tokenize, options, None);
+    let mut scored: Vec<(String, f64)> = tfidf_vectors
+        .iter()
+        .filter(|(other, _)| other.as_str() != question)
+        .map(|(other, vector)| (other.clone(), cosine_similarity(&query_vector, vector)))
+        .filter(|(_, similarity)| !similarity.is_nan())
+        .collect();
+
+    scored.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    scored.truncate(n);
+    scored
+}
+
+/// Returns the `n` stored questions most similar to the stored question `question`, excluding itself,
+/// comparing the stored vectors directly; None if `question` is not in the index
+/// Useful for "related questions" navigation, where the question is always one of our own
+fn nearest_to_question(
+    tfidf_vectors: &HashMap<String, HashMap<String, f64>>,
+    question: &str,
+    n: usize,
+) -> Option<Vec<(String, f64)>> {
+    let vector = tfidf_vectors.get(question)?;
+    let mut scored: Vec<(String, f64)> = tfidf_vectors
+        .iter()
+        .filter(|(other, _)| other.as_str() != question)
+        .map(|(other, other_vector)| (other.clone(), cosine_similarity(vector, other_vector)))
+        .filter(|(_, similarity)| !similarity.is_nan())
+        .collect();
+
+    scored.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    scored.truncate(n);
+    Some(scored)
+}
+
+/// Average pairwise cosine distance (1 - similarity) between the answers of all Q&A pairs
+/// Near 1.0 the answers cover distinct topics; near 0.0 the content is redundant. Corpora with fewer
+/// than two answers score 0.0. Aliases repeat their question's answer, so they are left out
+fn compute_answer_diversity_score(
+    qa_data: &HashMap<String, String>,
+    aliases: &HashMap<String, String>,
+    options: &TokenizerOptions,
+) -> f64 {
+    let entries: HashMap<String, String> = qa_data
+        .iter()
+        .filter(|(question, _)| !aliases.contains_key(*question))
+        .map(|(question, answer)| (question.clone(), answer.clone()))
+        .collect();
+    let (answer_vectors, _) =
+        compute_tfidf(&entries, Field::Answer, tokenize, options, &HashMap::new());
+    let vectors: Vec<&HashMap<String, f64>> = answer_vectors.values().collect();
+
+    let mut total_distance = 0.0;
+    let mut pairs = 0;
+    for i in 0..vectors.len() {
+        for j in i + 1..vectors.len() {
+            let similarity = cosine_similarity(vectors[i], vectors[j]);
+            // Answers without any weighted words share nothing with the others
+            let similarity = if similarity.is_nan() { 0.0 } else { similarity };
+            total_distance += 1.0 - similarity;
+            pairs += 1;
+        }
+    }
+
+    if pairs == 0 {
+        return 0.0;
+    }
+    total_distance / pairs as f64
+}
+
+/// Returns every question, sorted, whose tokens include `word` after the same normalization as the index
+/// This is a direct lookup rather than a ranked retrieval; there is no term index, so it scans the questions
+fn questions_containing_word<'a>(
+    qa_data: &'a HashMap<String, String>,
+    options: &TokenizerOptions,
+    word: &str,
+) -> Vec<&'a String> {
+    let Some(word) = tokenize(word, options).into_iter().next() else {
+        return Vec::new();
+    };
+
+    let mut questions: Vec<&String> = qa_data
+        .keys()
+        .filter(|question| tokenize(question, options).contains(&word))
+        .collect();
+    questions.sort();
+    questions
+}
+
+/// Returns up to `n` stored questions for autocompletion, case-insensitively matching `prefix`
+/// Questions starting with the prefix come first, then those containing it elsewhere, each group alphabetical
+fn suggest<'a>(qa_data: &'a HashMap<String, String>, prefix: &str, n: usize) -> Vec<&'a String> {
+    let prefix = prefix.to_lowercase();
+    let mut matches: Vec<(bool, &String)> = qa_data
+        .keys()
+        .filter_map(|question| {
+            let lowercase = question.to_lowercase();
+            if lowercase.starts_with(&prefix) {
+                Some((false, question))
+            } else if lowercase.contains(&prefix) {
+                Some((true, question))
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    matches.sort();
+    matches
+        .into_iter()
+        .take(n)
+        .map(|(_, question)| question)
+        .collect()
+}
+
+/// Fraction of an expected vocabulary that appears in the indexed questions, or 0.0 for an empty vocabulary
+fn compute_query_coverage(idf: &HashMap<String, f64>, vocabulary: &HashSet<String>) -> f64 {
+    if vocabulary.is_empty() {
+        return 0.0;
+    }
+    let covered = vocabulary
+        .iter()
+        .filter(|term| idf.contains_key(*term))
+        .count();
+    covered as f64 / vocabulary.len() as f64
+}
+
+/// Returns the domain terms that no indexed question contains, in the order given
+/// Terms are tokenized like the questions, so "Refunds" is found as "refunds"; a term that splits into several
+/// tokens counts as found only if every token is
+fn find_answer_gaps(
+    idf: &HashMap<String, f64>,
+    options: &TokenizerOptions,
+    domain_terms: &[&str],
+) -> Vec<String> {
+    domain_terms
+        .iter()
+        .filter(|term| {
+            let tokens = tokenize(term, options);
+            tokens.is_empty() || !tokens.iter().all(|token| idf.contains_key(token))
+        })
+        .map(|term| term.to_string())
+        .collect()
+}
+
+/// Suggests rephrasings of a question that match more precisely: every word whose IDF is below the corpus median
+/// (too common to discriminate) is swapped for the closest vocabulary word by edit distance among those at or
+/// above the median, giving one variant per swapped word
+fn suggest_question_improvements(
+    idf: &HashMap<String, f64>,
+    options: &TokenizerOptions,
+    question: &str,
+) -> Vec<String> {
+    let mut values: Vec<f64> = idf.values().copied().collect();
+    if values.is_empty() {
+        return Vec::new();
+    }
+    values.sort_by(f64::total_cmp);
+    let median = values[values.len() / 2];
+    let mut informative: Vec<(&String, f64)> = idf
+        .iter()
+        .filter(|(_, value)| **value >= median)
+        .map(|(word, value)| (word, *value))
+        .collect();
+    informative.sort_by(|a, b| a.0.cmp(b.0));
+
+    let words = tokenize(question, options);
+    let mut variants = Vec::new();
+    for (i, word) in words.iter().enumerate() {
+        if !idf.get(word).is_some_and(|value| *value < median) {
+            continue;
+        }
+        let replacement = informative
+            .iter()
+            .filter(|(candidate, _)| *candidate != word)
+            .min_by(|a, b| {
+                edit_distance(word, a.0)
+                    .cmp(&edit_distance(word, b.0))
+                    .then_with(|| b.1.total_cmp(&a.1))
+            });
+        if let Some((replacement, _)) = replacement {
+            let mut variant = words.clone();
+            variant[i] = replacement.to_string();
+            variants.push(variant.join(" "));
+        }
+    }
+    variants
+}
+
+/// Levenshtein distance between two words, counted in characters
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, char_a) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, char_b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(char_a != *char_b);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Returns the TF-IDF weight of each token of `question` as a query, heaviest first, to show which words
+/// drive its matching; words unknown to the index weigh 0.0
+fn compute_term_importance(
+    idf: &HashMap<String, f64>,
+    config: &Config,
+    question: &str,
+) -> Vec<(String, f64)> {
+    let mut weights: Vec<(String, f64)> =
+        compute_input_vector(question, idf, tokenize, &config.tokenizer, config.max_tf)
+            .into_iter()
+            .collect();
+    weights.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    weights
+}
+
+/// How a query relates to one stored question, for `/why`
+struct MatchExplanation {
+    similarity: f64,
+    /// Tokens found in both, sorted
+    shared: Vec<String>,
+    /// Tokens only the query has, sorted
+    query_only: Vec<String>,
+    /// Tokens only the question has, sorted
+    question_only: Vec<String>,
+}
+
+/// Compares a query against the stored question `question`, or returns None if it is not in the index
+fn explain_match(
+    tfidf_vectors: &HashMap<String, HashMap<String, f64>>,
+    idf: &HashMap<String, f64>,
+    config: &Config,
+    query: &str,
+    question: &str,
+) -> Option<MatchExplanation> {
+    let question_vector = tfidf_vectors.get(question)?;
+    let query_vector = compute_input_vector(query, idf, tokenize, &config.tokenizer, config.max_tf);
+
+    let sorted = |mut words: Vec<String>| {
+        words.sort();
+        words
+    };
+    Some(MatchExplanation {
+        similarity: cosine_similarity(&query_vector, question_vector),
+        shared: sorted(
+            query_vector
+                .keys()
+                .filter(|word| question_vector.contains_key(*word))
+                .cloned()
+                .collect(),
+        ),
+        query_only: sorted(
+            query_vector
+                .keys()
+                .filter(|word| !question_vector.contains_key(*word))
+                .cloned()
+                .collect(),
+        ),
+        question_only: sorted(
+            question_vector
+                .keys()
+                .filter(|word| !query_vector.contains_key(*word))
+                .cloned()
+                .collect(),
+        ),
+    })
+}
+
+/// Splits command arguments written as double-quoted strings, e.g. `"first one" "second"`
+/// Returns None if a quote is left open or there is text outside the quotes
+fn parse_quoted_args(args: &str) -> Option<Vec<String>> {
+    let mut parsed = Vec::new();
+    let mut rest = args.trim();
+    while !rest.is_empty() {
+        let inner = rest.strip_prefix('"')?;
+        let end = inner.find('"')?;
+        parsed.push(inner[..end].to_string());
+        rest = inner[end + 1..].trim_start();
+    }
+    Some(parsed)
+}
+
+/// Headline numbers about the loaded corpus
+struct CorpusSummary {
+    total_pairs: usize,
+    /// Distinct terms across all questions
+    vocabulary_size: usize,
+    avg_question_tokens: f64,
+    avg_answer_chars: f64,
+    /// (percentile, tokens) for the 50th, 90th and 99th percentile question length
+    question_token_percentiles: Vec<(usize, usize)>,
+    /// (percentile, characters) for the 50th, 90th and 99th percentile answer length
+    answer_char_percentiles: Vec<(usize, usize)>,
+    /// The most common terms (lowest IDF) with their IDF, most common first
+    top_terms: Vec<(String, f64)>,
+}
+
+/// Percentiles reported in the corpus summary
+const SUMMARY_PERCENTILES: [usize; 3] = [50, 90, 99];
+
+/// Number of most common terms reported in the corpus summary
+const SUMMARY_TOP_TERMS: usize = 10;
+
+/// Summarizes the size and shape of the corpus; averages and percentiles are 0 for an empty corpus
+/// Each Q&A pair counts once, however many aliases it has
+fn summarize_corpus(
+    qa_data: &HashMap<String, String>,
+    aliases: &HashMap<String, String>,
+    idf: &HashMap<String, f64>,
+    options: &TokenizerOptions,
+) -> CorpusSummary {
+    let entries: Vec<(&String, &String)> = qa_data
+        .iter()
+        .filter(|(question, _)| !aliases.contains_key(*question))
+        .collect();
+    let total_pairs = entries.len();
+    let average = |lengths: &[usize]| {
+        if total_pairs == 0 {
+            0.0
+        } else {
+            lengths.iter().sum::<usize>() as f64 / total_pairs as f64
+        }
+    };
+    // Nearest-rank percentiles over the sorted lengths
+    let percentiles = |lengths: &[usize]| {
+        SUMMARY_PERCENTILES
+            .iter()
+            .map(|&p| {
+                let rank = (p * lengths.len()).div_ceil(100);
+                (p, lengths.get(rank.saturating_sub(1)).copied().unwrap_or(0))
+            })
+            .collect()
+    };
+    let mut question_tokens: Vec<usize> = entries
+        .iter()
+        .map(|(question, _)| tokenize(question, options).len())
+        .collect();
+    question_tokens.sort();
+    let mut answer_chars: Vec<usize> = entries
+        .iter()
+        .map(|(_, answer)| answer.chars().count())
+        .collect();
+    answer_chars.sort();
+
+    let mut top_terms: Vec<(String, f64)> = idf
+        .iter()
+        .map(|(word, value)| (word.clone(), *value))
+        .collect();
+    top_terms.sort_by(|a, b| a.1.total_cmp(&b.1).then_with(|| a.0.cmp(&b.0)));
+    top_terms.truncate(SUMMARY_TOP_TERMS);
+
+    CorpusSummary {
+        total_pairs,
+        vocabulary_size: idf.len(),
+        avg_question_tokens: average(&question_tokens),
+        avg_answer_chars: average(&answer_chars),
+        question_token_percentiles: percentiles(&question_tokens),
+        answer_char_percentiles: percentiles(&answer_chars),
+        top_terms,
+    }
+}
+
+/// Renders a corpus summary as a single JSON object, for dashboards
+fn corpus_summary_json(summary: &CorpusSummary) -> Value {
+    let percentiles = |values: &[(usize, usize)]| {
+        values
+            .iter()
+            .map(|(p, value)| (format!("p{}", p), json!(value)))
+            .collect::<serde_json::Map<String, Value>>()
+    };
+    json!({
+        "question_count": summary.total_pairs,
+        "vocabulary_size": summary.vocabulary_size,
+        "avg_question_tokens": summary.avg_question_tokens,
+        "avg_answer_chars": summary.avg_answer_chars,
+        "question_tokens": percentiles(&summary.question_token_percentiles),
+        "answer_chars": percentiles(&summary.answer_char_percentiles),
+        "top_terms": summary
+            .top_terms
+            .iter()
+            .map(|(term, idf)| json!({ "term": term, "idf": idf }))
+            .collect::<Vec<Value>>(),
+    })
+}
+
+/// Returns the terms found in less than `threshold` of the questions, with the number of questions containing each
+/// Such thinly covered terms become unknown words if their few questions are edited or removed
+fn find_low_coverage_terms(
+    tfidf_vectors: &HashMap<String, HashMap<String, f64>>,
+    threshold: f64,
+) -> Vec<(String, usize)> {
+    let mut document_frequency: HashMap<&String, usize> = HashMap::new();
+    for vector in tfidf_vectors.values() {
+        for word in vector.keys() {
+            *document_frequency.entry(word).or_insert(0) += 1;
+        }
+    }
+
+    let total = tfidf_vectors.len() as f64;
+    let mut terms: Vec<(String, usize)> = document_frequency
+        .into_iter()
+        .filter(|(_, count)| (*count as f64 / total) < threshold)
+        .map(|(word, count)| (word.clone(), count))
+        .collect();
+    terms.sort_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.cmp(&b.0)));
+    terms
+}
+
+/// Renders question similarity as a Graphviz DOT graph
+/// Each question is a node labelled with its first 30 characters; an edge joins every pair of questions
+/// whose cosine similarity is at least `min_similarity`
+fn export_dot_graph(
+    tfidf_vectors: &HashMap<String, HashMap<String, f64>>,
+    min_similarity: f64,
+) -> String {
+    let mut questions: Vec<&String> = tfidf_vectors.keys().collect();
+    questions.sort();
+
+    let mut dot = String::from("digraph {\n");
+    for (i, question) in questions.iter().enumerate() {
+        let label: String = question.chars().take(30).collect();
+        dot.push_str(&format!(
+            "    q{} [label=\"{}\"];\n",
+            i,
+            label.replace('\\', "\\\\").replace('"', "\\\"")
+        ));
+    }
+    for i in 0..questions.len() {
+        for j in i + 1..questions.len() {
+            let similarity =
+                cosine_similarity(&tfidf_vectors[questions[i]], &tfidf_vectors[questions[j]]);
+            if similarity >= min_similarity {
+                dot.push_str(&format!(
+                    "    q{} -> q{} [label=\"{:.2}\"];\n",
+                    i,
+                    j,
+                    display_score(similarity)
+                ));
+            }
+        }
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+/// Computes the symmetric N×N cosine similarity matrix between all questions, in sorted question order
+/// Magnitudes are computed once per question; a question with no weighted terms is 1.0 similar to itself
+/// and 0.0 similar to everything else
+fn cosine_similarity_matrix(
+    tfidf_vectors: &HashMap<String, HashMap<String, f64>>,
+) -> (Vec<&String>, Vec<Vec<f64>>) {
+    let mut questions: Vec<&String> = tfidf_vectors.keys().collect();
+    questions.sort();
+    let magnitudes: Vec<f64> = questions
+        .iter()
+        .map(|q| {
+            tfidf_vectors[*q]
+                .values()
+                .map(|v| v * v)
+                .sum::<f64>()
+                .sqrt()
+        })
+        .collect();
+
+    let n = questions.len();
+    let mut matrix = vec![vec![0.0; n]; n];
+    for i in 0..n {
+        matrix[i][i] = 1.0;
+        for j in i + 1..n {
+            let (v1, v2) = (&tfidf_vectors[questions[i]], &tfidf_vectors[questions[j]]);
+            let dot_product: f64 = v1
+                .iter()
+                .map(|(word, value)| value * v2.get(word).unwrap_or(&0.0))
+                .sum();
+            let magnitude = magnitudes[i] * magnitudes[j];
+            let similarity = if magnitude > 0.0 {
+                dot_product / magnitude
+            } else {
+                0.0
+            };
+            matrix[i][j] = similarity;
+            matrix[j][i] = similarity;
+        }
+    }
+
+    (questions, matrix)
+}
+
+/// Returns the `k` most similar distinct question pairs from a similarity matrix, most similar first
+fn most_similar_pairs<'a>(
+    questions: &[&'a String],
+    matrix: &[Vec<f64>],
+    k: usize,
+) -> Vec<(&'a String, &'a String, f64)> {
+    let mut pairs = Vec::new();
+    for i in 0..questions.len() {
+        for j in i + 1..questions.len() {
+            pairs.push((questions[i], questions[j], matrix[i][j]));
+        }
+    }
+    pairs.sort_by(|a, b| {
+        b.2.total_cmp(&a.2)
+            .then_with(|| (a.0, a.1).cmp(&(b.0, b.1)))
+    });
+    pairs.truncate(k);
+    pairs
+}
+
+/// Renders the similarity matrix as CSV, with the questions as both the header row and the first column
+fn similarity_matrix_csv(questions: &[&String], matrix: &[Vec<f64>]) -> String {
+    let quote = |text: &str| format!("\"{}\"", text.replace('"', "\"\""));
+    let mut csv = String::from("question");
+    for question in questions {
+        csv.push(',');
+        csv.push_str(&quote(question));
+    }
+    csv.push('\n');
+    for (question, row) in questions.iter().zip(matrix) {
+        csv.push_str(&quote(question));
+        for similarity in row {
+            csv.push_str(&format!(",{:.4}", display_score(*similarity)));
+        }
+        csv.push('\n');
+    }
+    csv
+}
+
+/// Scores every candidate question against the input, skipping those with no defined similarity
+/// When a phonetic index is given, its similarity is blended in with weight `config.phonetic_weight`,
+/// and the share of query tokens found in order in the question with weight `config.subsequence_weight`
+/// When an answer index is given, a question scores at least its answer's coverage of the query, scaled by
+/// `config.answer_field_weight`
+fn score_candidates<'a>(
+    tfidf_vectors: &'a HashMap<String, HashMap<String, f64>>,
+    idf: &HashMap<String, f64>,
+    phonetic_index: Option<&TfidfIndex>,
+    answer_index: Option<&TfidfIndex>,
+    input: &str,
+    config: &Config,
+) -> Vec<(&'a String, f64)> {
+    let (input, emphasized) = strip_emphasis(input);
+    let mut input_vector =
+        compute_input_vector(&input, idf, tokenize, &config.tokenizer, config.max_tf);
+    // Words the user marked as important, like *refund*, weigh more in the query
+    for word in emphasized
+        .iter()
+        .flat_map(|word| tokenize(word, &config.tokenizer))
+    {
+        if let Some(weight) = input_vector.get_mut(&word) {
+            *weight *= config.emphasis_weight;
+        }
+    }
+
+    let phonetic_input = phonetic_index.map(|(_, phonetic_idf)| {
+        compute_input_vector(
+            &input,
+            phonetic_idf,
+            phonetic_tokenize,
+            &config.tokenizer,
+            config.max_tf,
+        )
+    });
+    let query_tokens = tokenize(&input, &config.tokenizer);
+    // Query words are weighed by their IDF among the answers; a word that no answer contains weighs as much
+    // as the rarest one, so an answer never covers a query about something it doesn't mention
+    let answer_weights: Option<Vec<(&String, f64)>> = answer_index.map(|(_, answer_idf)| {
+        let rarest = answer_idf.values().copied().fold(0.0, f64::max);
+        query_tokens
+            .iter()
+            .map(|token| (token, answer_idf.get(token).copied().unwrap_or(rarest)))
+            .collect()
+    });
+    let mut candidates = Vec::new();
+
+    for (question, vector) in tfidf_vectors {
+        // In match-all mode only questions containing every query token are candidates
+        if config.match_all && !input_vector.keys().all(|word| vector.contains_key(word)) {
+            continue;
+        }
+
+        let mut similarity = cosine_similarity(&input_vector, vector);
+        if let (Some((phonetic_vectors, _)), Some(phonetic_input)) =
+            (phonetic_index, &phonetic_input)
+        {
+            let phonetic_similarity =
+                cosine_similarity(phonetic_input, &phonetic_vectors[question]);
+            // A query made only of misspelled words has no word-level similarity (NaN), so count it as zero
+            if !phonetic_similarity.is_nan() {
+                let weight = config.phonetic_weight;
+                similarity = (1.0 - weight) * similarity.max(0.0) + weight * phonetic_similarity;
+            }
+        }
+        // Reward questions containing the query words in the same order, e.g. "reset password"
+        // in "how do I reset my password"
+        if config.subsequence_weight > 0.0 && !similarity.is_nan() && !query_tokens.is_empty() {
+            let question_tokens = tokenize(question, &config.tokenizer);
+            let overlap = longest_common_subsequence(&query_tokens, &question_tokens) as f64
+                / query_tokens.len() as f64;
+            let weight = config.subsequence_weight;
+            similarity = (1.0 - weight) * similarity + weight * overlap;
+        }
+        // Words found only in the answer, such as a product name, can still match, though an answer match
+        // never outscores a question match as good as `config.answer_field_weight`
+        if let (Some((answer_vectors, _)), Some(answer_weights)) = (answer_index, &answer_weights) {
+            let coverage = answer_coverage(answer_weights, &answer_vectors[question]);
+            if coverage > 0.0 {
+                // max() skips a NaN question similarity, as for a query of answer-only words
+                similarity = similarity.max(config.answer_field_weight * coverage);
+            }
+        }
+        if !similarity.is_nan() {
+            candidates.push((question, similarity));
+        }
+    }
+
+    candidates
+}
+
+/// Share of the query's weight, from 0.0 to 1.0, carried by the query words an answer contains
+fn answer_coverage(query_weights: &[(&String, f64)], answer_vector: &HashMap<String, f64>) -> f64 {
+    let total: f64 = query_weights.iter().map(|(_, weight)| weight).sum();
+    if total == 0.0 {
+        return 0.0;
+    }
+    let covered: f64 = query_weights
+        .iter()
+        .filter(|(word, _)| answer_vector.contains_key(*word))
+        .map(|(_, weight)| weight)
+        .sum();
+    covered / total
+}
+
+/// Picks the highest scoring candidate, or None if there are no candidates
+fn best_candidate<'a>(
+    qa_data: &HashMap<String, String>,
+    candidates: &[(&'a String, f64)],
+    config: &Config,
+) -> Option<(&'a String, f64)> {
+    let mut best: Option<(&String, f64)> = None;
+
+    for &(question, similarity) in candidates {
+        let Some((best_question, max_similarity)) = best else {
+            best = Some((question, similarity));
+            continue;
+        };
+
+        // On a tie, optionally prefer the richer answer, counting at most `cap` characters of it
+        let wins_tie = config.prefer_longer_answers.is_some_and(|cap| {
+            let answer_len = |q: &str| qa_data[q].chars().count().min(cap);
+            (similarity - max_similarity).abs() < 1e-9
+                && answer_len(question) > answer_len(best_question)
+        });
+        if similarity > max_similarity || wins_tie {
+            best = Some((question, similarity));
+        }
+    }
+
+    best
+}
+
+/// Returns the answer to the input from its scored candidates and the best of them (see `best_candidate`),
+/// or a fallback or apology when the best one doesn't clear the threshold
+fn get_response(
+    qa_data: &HashMap<String, String>,
+    aliases: &HashMap<String, String>,
+    idf: &HashMap<String, f64>,
+    input: &str,
+    candidates: &mut [(&String, f64)],
+    best: Option<(&String, f64)>,
+    config: &Config,
+) -> String {
+    if let Some((question, similarity)) = best {
+        if similarity > config.threshold {
+            let answer = &qa_data[question];
+            if let Some(lang) = config.answer_language {
+                if !is_language(answer, lang) {
+                    return format!(
+                        "I'm sorry, I don't have an answer to that in {}.",
+                        lang.eng_name()
+                    );
+                }
+            }
+            let answer = post_process_answer(&linked_answer(qa_data, question), config);
+            if config.echo_question {
+                return format!("Q: {}\nA: {}", primary_question(aliases, question), answer);
+            }
+            return answer;
+        }
+    }
+
+    if config.answer_search_fallback {
+        if let Some(response) =
+            answer_search_fallback(qa_data, aliases, idf, &config.tokenizer, input)
+        {
+            return response;
+        }
+    }
+
+    if config.keyword_fallback {
+        if let Some((question, _)) = keyword_fallback(qa_data, &config.tokenizer, input) {
+            return format!(
+                "I couldn't find a close match, but this entry mentions some of your keywords (keyword match, not a semantic match):\nQ: {}\nA: {}",
+                primary_question(aliases, question),
+                linked_answer(qa_data, question)
+            );
+        }
+    }
+
+    // With no candidates, or `--suggestions 0`, there is nothing to suggest
+    match best {
+        Some(_) if config.suggestions > 1 => {
+            if config.diversify_suggestions {
+                diversify(qa_data, aliases, candidates);
+            }
+            candidates.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+            let list: Vec<String> = candidates
+                .iter()
+                .take(config.suggestions)
+                .enumerate()
+                .map(|(i, (question, _))| format!("  {}. {}", i + 1, question))
+                .collect();
+            format!("I'm sorry, I don't have specific information about that. The closest questions I can answer are:\n{}\nWould you like me to answer one of those instead?", list.join("\n"))
+        }
+        Some((question, _)) if config.suggestions == 1 => {
+            format!("I'm sorry, I don't have specific information about that. The closest question I can answer is: '{}'. Would you like me to answer that instead?", question)
+        }
+        _ => "I'm sorry, I couldn't find a relevant question. Please try rephrasing your question."
+            .to_string(),
+    }
+}
+
+/// Divides each candidate's score by how many questions in the corpus share its answer, so an answer
+/// repeated under many phrasings cannot crowd distinct answers out of a ranked list
+/// Aliases are not counted, as they are phrasings of one entry rather than separate entries
+fn diversify(
+    qa_data: &HashMap<String, String>,
+    aliases: &HashMap<String, String>,
+    candidates: &mut [(&String, f64)],
+) {
+    let mut answer_frequency: HashMap<&String, usize> = HashMap::new();
+    for (_, answer) in qa_data
+        .iter()
+        .filter(|(question, _)| !aliases.contains_key(*question))
+    {
+        *answer_frequency.entry(answer).or_insert(0) += 1;
+    }
+    for (question, similarity) in candidates.iter_mut() {
+        let frequency = answer_frequency.get(&qa_data[*question]).copied();
+        *similarity /= frequency.unwrap_or(1) as f64;
+    }
+}
+
+/// Expands `[[question]]` references in an answer into the referenced question's answer, recursively
+/// `visiting` holds the questions being expanded; a reference back to one of them (a cycle), or to a question
+/// that doesn't exist, is left as the quoted question text instead
+fn resolve_links<'a>(
+    qa_data: &'a HashMap<String, String>,
+    answer: &str,
+    visiting: &mut Vec<&'a str>,
+) -> String {
+    let mut resolved = String::new();
+    let mut rest = answer;
+    while let Some(start) = rest.find("[[") {
+        let Some(length) = rest[start + 2..].find("]]") else {
+            break;
+        };
+        resolved.push_str(&rest[..start]);
+        let key = &rest[start + 2..start + 2 + length];
+        match qa_data.get_key_value(key) {
+            Some((question, target)) if !visiting.contains(&question.as_str()) => {
+                visiting.push(question);
+                resolved.push_str(&resolve_links(qa_data, target, visiting));
+                visiting.pop();
+            }
+            _ => resolved.push_str(&format!("\"{}\"", key)),
+        }
+        rest = &rest[start + 2 + length + 2..];
+    }
+    resolved.push_str(rest);
+    resolved
+}
+
+/// Returns the answer to `question` as it is shown, with its `[[question]]` references expanded
+fn linked_answer<'a>(qa_data: &'a HashMap<String, String>, question: &'a str) -> String {
+    resolve_links(qa_data, &qa_data[question], &mut vec![question])
+}
+
+/// Transforms a matched answer before it is shown, e.g. to add a signature
+/// With `--answer-template`, the answer replaces `{answer}` in the template; otherwise it is returned unchanged
+fn post_process_answer(answer: &str, config: &Config) -> String {
+    match &config.answer_template {
+        Some(template) => template.replace("{answer}", answer),
+        None => answer.to_string(),
+    }
+}
+
+/// One answered query, with the question that matched and how well
+struct QueryResult<'a> {
+    query: &'a str,
+    answer: String,
+    /// The question whose answer was given, if one cleared the threshold; never an alias
+    matched_question: Option<&'a String>,
+    /// Similarity of the best matching question, whether or not it cleared the threshold
+    score: Option<f64>,
+}
+
+/// Renders one batch result as a line of the chosen format; CSV fields are quoted when needed and TSV
+/// fields escape tabs, line breaks and backslashes, so every result stays on one line
+fn format_batch_result(result: &QueryResult, format: OutputFormat) -> String {
+    let matched = result.matched_question.map(String::as_str);
+    let score = result.score.map(|score| format!("{:.3}", score));
+    match format {
+        OutputFormat::Text => result.answer.clone(),
+        OutputFormat::Jsonl => json!({
+            "query": result.query,
+            "answer": result.answer,
+            "matched_question": matched,
+            "score": result.score,
+        })
+        .to_string(),
+        OutputFormat::Csv => {
+            let field = |text: &str| {
+                if text.contains([',', '"', '\n', '\r']) {
+                    format!("\"{}\"", text.replace('"', "\"\""))
+                } else {
+                    text.to_string()
+                }
+            };
+            [
+                result.query,
+                &result.answer,
+                matched.unwrap_or(""),
+                score.as_deref().unwrap_or(""),
+            ]
+            .map(field)
+            .join(",")
+        }
+        OutputFormat::Tsv => {
+            let field = |text: &str| {
+                text.replace('\\', "\\\\")
+                    .replace('\t', "\\t")
+                    .replace('\n', "\\n")
+                    .replace('\r', "\\r")
+            };
+            [
+                result.query,
+                &result.answer,
+                matched.unwrap_or(""),
+                score.as_deref().unwrap_or(""),
+            ]
+            .map(field)
+            .join("\t")
+        }
+    }
+}
+
+/// Whether `text` is written in `lang`
+/// Text whose language cannot be detected reliably (e.g. very short answers) is given the benefit of the doubt
+fn is_language(text: &str, lang: Lang) -> bool {
+    match whatlang::detect(text) {
+        Some(info) if info.is_reliable() => info.lang() == lang,
+        _ => true,
+    }
+}
+
+/// How many of a set of expected queries the corpus answers with confidence
+struct CoverageReport {
+    total: usize,
+    covered: usize,
+    /// Queries whose best score fell below the threshold
+    uncovered: Vec<String>,
+}
+
+impl CoverageReport {
+    /// Fraction of queries covered; an empty query set counts as fully covered
+    fn coverage_rate(&self) -> f64 {
+        if self.total == 0 {
+            return 1.0;
+        }
+        self.covered as f64 / self.total as f64
+    }
+}
+
+/// Runs every test query and classifies it as covered when its best match clears `config.threshold`
+fn assert_coverage(
+    qa_data: &HashMap<String, String>,
+    tfidf_vectors: &HashMap<String, HashMap<String, f64>>,
+    idf: &HashMap<String, f64>,
+    phonetic_index: Option<&TfidfIndex>,
+    answer_index: Option<&TfidfIndex>,
+    test_queries: &[String],
+    config: &Config,
+) -> CoverageReport {
+    let mut uncovered = Vec::new();
+
+    for query in test_queries {
+        let candidates = score_candidates(
+            tfidf_vectors,
+            idf,
+            phonetic_index,
+            answer_index,
+            query,
+            config,
+        );
+        let covered = best_candidate(qa_data, &candidates, config)
+            .is_some_and(|(_, similarity)| similarity > config.threshold);
+        if !covered {
+            uncovered.push(query.clone());
+        }
+    }
+
+    CoverageReport {
+        total: test_queries.len(),
+        covered: test_queries.len() - uncovered.len(),
+        uncovered,
+    }
+}
+
+/// A stored question that, asked verbatim, does not resolve to itself with confidence
+struct SelfMatchFailure {
+    question: String,
+    /// The question that won instead, or None if the question itself won below the threshold
+    matched: Option<String>,
+    similarity: f64,
+}
+
+/// Asks every stored question verbatim and reports those not answered by themselves above
+/// `config.threshold`, a sign of ambiguous or overlapping questions in the dataset
+fn self_test(
+    qa_data: &HashMap<String, String>,
+    tfidf_vectors: &HashMap<String, HashMap<String, f64>>,
+    idf: &HashMap<String, f64>,
+    phonetic_index: Option<&TfidfIndex>,
+    answer_index: Option<&TfidfIndex>,
+    config: &Config,
+) -> Vec<SelfMatchFailure> {
+    let mut questions: Vec<&String> = qa_data.keys().collect();
+    questions.sort();
+    let mut failures = Vec::new();
+
+    for question in questions {
+        let candidates = score_candidates(
+            tfidf_vectors,
+            idf,
+            phonetic_index,
+            answer_index,
+            question,
+            config,
+        );
+        let best = best_candidate(qa_data, &candidates, config);
+        let (matched, similarity) = match best {
+            Some((matched, similarity)) => (Some(matched), similarity),
+            None => (None, 0.0),
+        };
+        if matched == Some(question) && similarity > config.threshold {
+            continue;
+        }
+        failures.push(SelfMatchFailure {
+            question: question.clone(),
+            matched: matched.filter(|&m| m != question).cloned(),
+            similarity,
+        });
+    }
+
+    failures
+}
+
+/// Retrieval quality measured by k-fold cross-validation
+struct CrossValidationReport {
+    folds: usize,
+    /// Mean over folds of the fraction of held-out questions whose top match has the same answer
+    mean_precision_at_1: f64,
+    per_fold_scores: Vec<f64>,
+}
+
+/// Splits the questions into `folds` groups, then for each group indexes the other questions and asks every
+/// held-out question, counting a hit when the top match carries the held-out question's answer
+/// Only questions that share their answer with a paraphrase can ever be hits, so the score reflects how well
+/// paraphrases find each other
+fn cross_validate(
+    qa_data: &HashMap<String, String>,
+    folds: usize,
+    config: &Config,
+) -> CrossValidationReport {
+    let mut questions: Vec<&String> = qa_data.keys().collect();
+    questions.sort();
+
+    let mut per_fold_scores = Vec::new();
+    for fold in 0..folds {
+        let in_fold = |(i, _): &(usize, &&String)| i % folds == fold;
+        let held_out: Vec<&String> = questions
+            .iter()
+            .enumerate()
+            .filter(in_fold)
+            .map(|(_, question)| *question)
+            .collect();
+        let training: HashMap<String, String> = questions
+            .iter()
+            .enumerate()
+            .filter(|entry| !in_fold(entry))
+            .map(|(_, question)| ((*question).clone(), qa_data[*question].clone()))
+            .collect();
+        let (tfidf_vectors, idf) = compute_tfidf(
+            &training,
+            Field::Question,
+            tokenize,
+            &config.tokenizer,
+            &config.term_boost,
+        );
+
+        let hits = held_out
+            .iter()
+            .filter(|question| {
+                let candidates =
+                    score_candidates(&tfidf_vectors, &idf, None, None, question, config);
+                best_candidate(&training, &candidates, config)
+                    .is_some_and(|(matched, _)| training[matched] == qa_data[**question])
+            })
+            .count();
+        per_fold_scores.push(hits as f64 / held_out.len() as f64);
+    }
+
+    CrossValidationReport {
+        folds,
+        mean_precision_at_1: per_fold_scores.iter().sum::<f64>() / folds as f64,
+        per_fold_scores,
+    }
+}
+
+/// Looks for query words in the answer text when no question matched well enough
+/// Only words outside the question vocabulary are used: those were already scored by TF-IDF, and this
+/// keeps common words such as "what" from matching every answer
+/// Aliases are skipped, so each answer is listed once
+fn answer_search_fallback(
+    qa_data: &HashMap<String, String>,
+    aliases: &HashMap<String, String>,
+    idf: &HashMap<String, f64>,
+    options: &TokenizerOptions,
+    input: &str,
+) -> Option<String> {
+    // Answers are prose, so compare words with surrounding punctuation trimmed off
+    let trim = |word: &str| {
+        word.trim_matches(|c: char| !c.is_alphanumeric())
+            .to_string()
+    };
+    let words: Vec<String> = tokenize(input, options)
+        .into_iter()
+        .filter(|word| !idf.contains_key(word))
+        .map(|word| trim(&word))
+        .filter(|word| !word.is_empty())
+        .collect();
+
+    let mut matches: Vec<(&String, &String)> = qa_data
+        .iter()
+        .filter(|(question, _)| !aliases.contains_key(*question))
+        .filter(|(_, answer)| {
+            let answer_words: Vec<String> =
+                tokenize(answer, options).iter().map(|w| trim(w)).collect();
+            words.iter().any(|word| answer_words.contains(word))
+        })
+        .collect();
+    if matches.is_empty() {
+        return None;
+    }
+    matches.sort();
+
+    let answers: Vec<String> = matches
+        .iter()
+        .map(|(question, _)| linked_answer(qa_data, question))
+        .collect();
+    Some(format!(
+        "I found this in our knowledge base:\n{}",
+        answers.join("\n")
+    ))
+}
+
+/// Finds the entry whose question or answer text literally contains the most query keywords
+/// Keywords found in every entry (such as "what") say nothing about the query and are ignored
+fn keyword_fallback<'a>(
+    qa_data: &'a HashMap<String, String>,
+    options: &TokenizerOptions,
+    input: &str,
+) -> Option<(&'a String, &'a String)> {
+    let trim = |word: &str| {
+        word.trim_matches(|c: char| !c.is_alphanumeric())
+            .to_string()
+    };
+    let mut keywords: Vec<String> = tokenize(input, options)
+        .iter()
+        .map(|word| trim(word))
+        .filter(|word| !word.is_empty())
+        .collect();
+    keywords.sort();
+    keywords.dedup();
+
+    let mut entries: Vec<(&String, &String, HashSet<String>)> = qa_data
+        .iter()
+        .map(|(question, answer)| {
+            let words = tokenize(&format!("{} {}", question, answer), options)
+                .iter()
+                .map(|word| trim(word))
+                .collect();
+            (question, answer, words)
+        })
+        .collect();
+    entries.sort_by(|a, b| a.0.cmp(b.0));
+    keywords.retain(|keyword| !entries.iter().all(|(_, _, words)| words.contains(keyword)));
+
+    let mut best: Option<(&String, &String, usize)> = None;
+    for (question, answer, words) in &entries {
+        let hits = keywords.iter().filter(|k| words.contains(*k)).count();
+        if hits > 0 && best.is_none_or(|(_, _, most)| hits > most) {
+            best = Some((question, answer, hits));
+        }
+    }
+    best.map(|(question, answer, _)| (question, answer))
+}
+
+/// Computes the TF-IDF vector for the input question, tokenized the same way as the corpus behind `idf`
+/// With `max_tf`, a word repeated in the query counts at most that many times, so "refund refund refund"
+/// cannot drown out the other words the user typed
+fn compute_input_vector(
+    input: &str,
+    idf: &HashMap<String, f64>,
+    tokenizer: Tokenizer,
+    options: &TokenizerOptions,
+    max_tf: Option<usize>,
+) -> HashMap<String, f64> {
+    let words = tokenizer(input, options);
+    let mut tf: HashMap<String, usize> = HashMap::new();
+    for word in &words {
+        *tf.entry(word.clone()).or_insert(0) += 1;
+    }
+    if let Some(max_tf) = max_tf {
+        tf.values_mut()
+            .for_each(|count| *count = (*count).min(max_tf));
+    }
+    let word_count: usize = tf.values().sum();
+
+    let mut tfidf = HashMap::new();
+    for (word, count) in tf {
+        let tf = count as f64 / word_count as f64;
+        let idf_value = idf.get(&word).unwrap_or(&0.0);
+        tfidf.insert(word, tf * idf_value);
+    }
+    tfidf
+}
+
+/// Length of the longest common subsequence of two token lists, i.e. how many tokens they share in the same order
+fn longest_common_subsequence(a: &[String], b: &[String]) -> usize {
+    let mut previous = vec![0; b.len() + 1];
+    for token_a in a {
+        let mut current = vec![0; b.len() + 1];
+        for (j, token_b) in b.iter().enumerate() {
+            current[j + 1] = if token_a == token_b {
+                previous[j] + 1
+            } else {
+                current[j].max(previous[j + 1])
+            };
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Clamps a similarity to [0.0, 1.0] for display, so rounding error or score blending never shows as "1.0001"
+/// Ranking always uses the raw value
+fn display_score(similarity: f64) -> f64 {
+    similarity.clamp(0.0, 1.0)
+}
+
+/// Calculates the cosine similarity between two TF-IDF vectors
+fn cosine_similarity(v1: &HashMap<String, f64>, v2: &HashMap<String, f64>) -> f64 {
+    let mut dot_product = 0.0;
+    let mut mag1 = 0.0;
+    let mut mag2 = 0.0;
+
+    for (word, value) in v1 {
+        dot_product += value * v2.get(word).unwrap_or(&0.0);
+        mag1 += value * value;
+    }
+
+    for value in v2.values() {
+        mag2 += value * value;
+    }
+
+    dot_product / (mag1.sqrt() * mag2.sqrt())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A small support corpus in which each test query has a single best match
+    const PAIRS: [(&str, &str); 5] = [
+        ("What does EVA do?", "EVA verifies patient eligibility."),
+        ("What does CAM do?", "CAM processes claims."),
+        ("How does PHIL work?", "PHIL posts payments."),
+        (
+            "What is the refund policy?",
+            "Refunds are issued within 30 days.",
+        ),
+        (
+            "How do I reset my password?",
+            "Use the 'Forgot password' link.",
+        ),
+    ];
+
+    /// Builds Q&A data from (question, answer) pairs
+    fn qa(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(question, answer)| (question.to_string(), answer.to_string()))
+            .collect()
+    }
+
+    /// A corpus indexed the way `main` indexes it, ready to answer queries
+    struct Fixture {
+        qa_data: HashMap<String, String>,
+        aliases: HashMap<String, String>,
+        tfidf_vectors: HashMap<String, HashMap<String, f64>>,
+        idf: HashMap<String, f64>,
+        phonetic_index: Option<TfidfIndex>,
+        answer_index: Option<TfidfIndex>,
+        config: Config,
+    }
+
+    impl Fixture {
+        fn new(pairs: &[(&str, &str)], config: Config) -> Self {
+            Self::from_corpus((qa(pairs), HashMap::new()), config)
+        }
+
+        fn from_corpus(corpus: Corpus, config: Config) -> Self {
+            let index = compute_tfidf(
+                &corpus.0,
+                Field::Question,
+                tokenize,
+                &config.tokenizer,
+                &config.term_boost,
+            );
+            Self::with_index(corpus, index, config)
+        }
+
+        /// Like `from_corpus`, but with the question index given rather than computed
+        fn with_index(
+            (qa_data, aliases): Corpus,
+            (tfidf_vectors, idf): TfidfIndex,
+            config: Config,
+        ) -> Self {
+            let (phonetic_index, answer_index) = compute_secondary_indexes(&qa_data, &config);
+            Fixture {
+                qa_data,
+                aliases,
+                tfidf_vectors,
+                idf,
+                phonetic_index,
+                answer_index,
+                config,
+            }
+        }
+
+        fn session(&self) -> Session<'_> {
+            Session {
+                qa_data: &self.qa_data,
+                aliases: &self.aliases,
+                tfidf_vectors: &self.tfidf_vectors,
+                idf: &self.idf,
+                phonetic_index: self.phonetic_index.as_ref(),
+                answer_index: self.answer_index.as_ref(),
+                config: &self.config,
+                history: Vec::new(),
+            }
+        }
+
+        fn answer(&self, query: &str) -> String {
+            self.session().answer_query(query).answer
+        }
+
+        /// Each candidate question's score for `query`
+        fn scores(&self, query: &str) -> HashMap<&String, f64> {
+            score_candidates(
+                &self.tfidf_vectors,
+                &self.idf,
+                self.phonetic_index.as_ref(),
+                self.answer_index.as_ref(),
+                query,
+                &self.config,
+            )
+            .into_iter()
+            .collect()
+        }
+    }
+
+    #[test]
+    fn jsonl_round_trip_answers_like_the_original() {
+        let original = Fixture::new(&PAIRS, Config::default());
+        let mut buffer = Vec::new();
+        to_jsonl_stream(&original.qa_data, &original.aliases, &mut buffer).unwrap();
+        let (corpus, index) = from_jsonl_stream(&buffer[..], false).unwrap();
+        assert!(index.is_none());
+        let restored = Fixture::from_corpus(corpus, Config::default());
+
+        assert_eq!(restored.qa_data, original.qa_data);
+        for query in [
+            "what does eva do",
+            "cam",
+            "how does phil work",
+            "refund",
+            "weather today",
+        ] {
+            assert_eq!(
+                restored.answer(query),
+                original.answer(query),
+                "query '{}'",
+                query
+            );
+        }
+    }
+
+    /// Writes `contents` to a file named `name` in the temporary directory, unique to this test run
+    fn temp_file(name: &str, contents: &[u8]) -> String {
+        let path = std::env::temp_dir().join(format!("thoughtful-ai-{}-{}", process::id(), name));
+        std::fs::write(&path, contents).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn comment_fields_are_loaded_but_not_indexed() {
+        let path = temp_file(
+            "comment.json",
+            br#"{"questions": [
+                {"question": "What does EVA do?", "answer": "EVA verifies eligibility.",
+                 "_comment": "reviewed by billing"}
+            ]}"#,
+        );
+        let (corpus, _) = initialize_qa_data(&path, &RetryPolicy::default(), false).unwrap();
+        let fixture = Fixture::from_corpus(corpus, Config::default());
+
+        assert_eq!(
+            fixture.qa_data,
+            qa(&[("What does EVA do?", "EVA verifies eligibility.")])
+        );
+        assert!(!fixture.idf.contains_key("reviewed"));
+        assert!(!fixture.idf.contains_key("billing"));
+    }
+
+    #[test]
+    fn deduplication_keeps_the_shortest_question() {
+        let mut qa_data = qa(&[
+            ("How do refunds work?", "Refunds take 30 days."),
+            ("How long do refunds take?", "Refunds take  30 days."),
+            ("When will I get my money back?", "refunds take 30 days."),
+            ("What does EVA do?", "EVA verifies eligibility."),
+        ]);
+        let report = deduplicate_answers(&mut qa_data, &mut HashMap::new());
+
+        assert_eq!(report.removed_count, 2);
+        assert_eq!(
+            report.groups,
+            [[
+                "How do refunds work?",
+                "How long do refunds take?",
+                "When will I get my money back?"
+            ]]
+        );
+        let fixture = Fixture::from_corpus((qa_data, HashMap::new()), Config::default());
+        assert_eq!(fixture.qa_data.len(), 2);
+        assert_eq!(
+            fixture.answer("How do refunds work?"),
+            "Refunds take 30 days."
+        );
+    }
+
+    #[test]
+    fn transient_errors_are_retried() {
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            backoff_ms: 0,
+        };
+        let mut attempts = 0;
+        let loaded = with_retry("qa_data.json", &policy, || {
+            attempts += 1;
+            if attempts < 3 {
+                Err(io::Error::other("file is locked"))
+            } else {
+                Ok("loaded")
+            }
+        });
+
+        assert_eq!(loaded.unwrap(), "loaded");
+        assert_eq!(attempts, 3);
+    }
+
+    #[test]
+    fn missing_files_are_not_retried() {
+        let mut attempts = 0;
+        let loaded: io::Result<()> = with_retry("qa_data.json", &RetryPolicy::default(), || {
+            attempts += 1;
+            Err(io::ErrorKind::NotFound.into())
+        });
+
+        assert!(loaded.is_err());
+        assert_eq!(attempts, 1);
+    }
+
+    #[test]
+    fn match_all_rejects_questions_missing_a_query_word() {
+        let query = "the refund policy? today";
+        let any = Fixture::new(&PAIRS, Config::default());
+        let all = Fixture::new(
+            &PAIRS,
+            Config {
+                match_all: true,
+                ..Config::default()
+            },
+        );
+
+        assert_eq!(any.answer(query), "Refunds are issued within 30 days.");
+        assert_eq!(
+            all.answer(query),
+            "I'm sorry, I couldn't find a relevant question. Please try rephrasing your question."
+        );
+    }
+
+    #[test]
+    fn phonetic_matching_finds_misspelled_names() {
+        let words_only = Fixture::new(&PAIRS, Config::default());
+        let phonetic = Fixture::new(
+            &PAIRS,
+            Config {
+                phonetic_weight: 0.8,
+                ..Config::default()
+            },
+        );
+
+        assert_eq!(
+            words_only.answer("eeva"),
+            "I'm sorry, I couldn't find a relevant question. Please try rephrasing your question."
+        );
+        assert_eq!(phonetic.answer("eeva"), "EVA verifies patient eligibility.");
+    }
+
+    #[test]
+    fn transcript_lists_the_session_in_order() {
+        let fixture = Fixture::new(&PAIRS, Config::default());
+        let path = temp_file("transcript.txt", b"");
+        let mut session = fixture.session();
+        session.handle_line("what does eva do");
+        session.handle_line("how does phil work");
+        session.handle_line(&format!("/transcript {}", path));
+
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "Q: what does eva do\nA: EVA verifies patient eligibility.\n\n\
+             Q: how does phil work\nA: PHIL posts payments.\n\n"
+        );
+    }
+
+    #[test]
+    fn commands_match_whole_words_only() {
+        let fixture = Fixture::new(&PAIRS, Config::default());
+        let mut session = fixture.session();
+        session.handle_line("/search able");
+        session.handle_line("/searchable");
+
+        // Only the second line is a question, so only it is kept for the transcript
+        assert_eq!(session.history.len(), 1);
+        assert_eq!(session.history[0].0, "/searchable");
+    }
+
+    #[test]
+    fn ties_go_to_the_longer_answer() {
+        let pairs = [
+            ("EVA pricing", "Ask sales."),
+            (
+                "EVA billing",
+                "EVA is billed monthly per verified patient, with no setup fee.",
+            ),
+            ("CAM claims", "CAM processes claims."),
+        ];
+        let config = Config {
+            threshold: 0.1,
+            prefer_longer_answers: Some(200),
+            ..Config::default()
+        };
+        let fixture = Fixture::new(&pairs, config);
+
+        let candidates = score_candidates(
+            &fixture.tfidf_vectors,
+            &fixture.idf,
+            None,
+            None,
+            "eva",
+            &fixture.config,
+        );
+        let score = |question: &str| {
+            candidates
+                .iter()
+                .find(|(candidate, _)| candidate.as_str() == question)
+                .map(|(_, score)| *score)
+        };
+        assert_eq!(score("EVA pricing"), score("EVA billing"));
+        assert_eq!(
+            fixture.answer("eva"),
+            "EVA is billed monthly per verified patient, with no setup fee."
+        );
+    }
+
+    #[test]
+    fn answer_search_is_a_case_insensitive_substring_match() {
+        let qa_data = qa(&PAIRS);
+        let questions = |pattern: &str| -> Vec<&String> {
+            search_answers(&qa_data, &HashMap::new(), pattern)
+                .into_iter()
+                .map(|(question, _)| question)
+                .collect()
+        };
+
+        assert_eq!(questions("PAYMENTS"), ["How does PHIL work?"]);
+        assert_eq!(
+            questions("pa"),
+            [
+                "How do I reset my password?",
+                "How does PHIL work?",
+                "What does EVA do?"
+            ]
+        );
+        assert!(questions("invoice").is_empty());
+    }
+
+    #[test]
+    fn answer_search_fallback_finds_answer_only_words() {
+        let fixture = Fixture::new(
+            &PAIRS,
+            Config {
+                answer_search_fallback: true,
+                ..Config::default()
+            },
+        );
+
+        assert_eq!(
+            fixture.answer("eligibility"),
+            "I found this in our knowledge base:\nEVA verifies patient eligibility."
+        );
+        assert_eq!(
+            fixture.answer("weather"),
+            "I'm sorry, I couldn't find a relevant question. Please try rephrasing your question."
+        );
+    }
+
+    #[test]
+    fn dot_graph_links_similar_questions() {
+        let fixture = Fixture::new(&PAIRS, Config::default());
+        let dot = export_dot_graph(&fixture.tfidf_vectors, 0.3);
+
+        assert!(dot.starts_with("digraph {\n"));
+        // Only the EVA and CAM questions are alike; they sort third and fourth
+        let edges: Vec<&str> = dot.lines().filter(|line| line.contains("->")).collect();
+        assert_eq!(edges.len(), 1);
+        assert!(edges[0].starts_with("    q2 -> q3 "));
+        assert!(dot.contains("q2 [label=\"What does CAM do?\"]"));
+        assert!(dot.contains("q3 [label=\"What does EVA do?\"]"));
+    }
+
+    #[test]
+    fn fallback_suggests_the_closest_questions_by_score() {
+        let with_suggestions = |suggestions| {
+            Fixture::new(
+                &PAIRS,
+                Config {
+                    suggestions,
+                    ..Config::default()
+                },
+            )
+        };
+
+        assert_eq!(
+            with_suggestions(3).answer("what does it cost"),
+            "I'm sorry, I don't have specific information about that. The closest questions I can answer are:\n\
+             \x20 1. What does CAM do?\n  2. What does EVA do?\n  3. How does PHIL work?\n\
+             Would you like me to answer one of those instead?"
+        );
+        assert_eq!(
+            with_suggestions(0).answer("what does it cost"),
+            "I'm sorry, I couldn't find a relevant question. Please try rephrasing your question."
+        );
+    }
+
+    #[test]
+    fn coverage_rate_counts_confident_answers() {
+        let fixture = Fixture::new(&PAIRS, Config::default());
+        let queries: Vec<String> = [
+            "what does eva do",
+            "eva",
+            "what does cam do",
+            "cam",
+            "how does phil work",
+            "phil",
+            "refund policy?",
+            "reset password?",
+            "my password?",
+            "weather",
+        ]
+        .map(String::from)
+        .to_vec();
+        let report = assert_coverage(
+            &fixture.qa_data,
+            &fixture.tfidf_vectors,
+            &fixture.idf,
+            None,
+            None,
+            &queries,
+            &fixture.config,
+        );
+
+        assert_eq!((report.total, report.covered), (10, 9));
+        assert_eq!(report.uncovered, ["weather"]);
+        assert_eq!(report.coverage_rate(), 0.9);
+    }
+
+    #[test]
+    fn boosted_terms_outrank_equal_matches() {
+        let pairs = [
+            ("What is the pricing", "Plans start at $100."),
+            ("What are the plans", "Basic and Pro."),
+            ("How does PHIL work", "PHIL posts payments."),
+        ];
+        let plain = Fixture::new(&pairs, Config::default());
+        let boosted = Fixture::new(
+            &pairs,
+            Config {
+                term_boost: HashMap::from([("pricing".to_string(), 2.0)]),
+                ..Config::default()
+            },
+        );
+        let pricing = "What is the pricing".to_string();
+        let plans = "What are the plans".to_string();
+
+        let scores = plain.scores("pricing plans");
+        assert!((scores[&pricing] - scores[&plans]).abs() < 1e-12);
+        let scores = boosted.scores("pricing plans");
+        assert!(scores[&pricing] > scores[&plans]);
+        assert_eq!(boosted.answer("pricing plans"), "Plans start at $100.");
+    }
+
+    #[test]
+    fn questions_of_only_common_words_are_unmatchable() {
+        let fixture = Fixture::new(
+            &[
+                ("What is EVA", "EVA verifies patient eligibility."),
+                ("What is CAM", "CAM processes claims."),
+                ("What is PHIL", "PHIL posts payments."),
+                ("What is", "Ask me about EVA, CAM or PHIL."),
+            ],
+            Config::default(),
+        );
+
+        assert_eq!(
+            find_unmatchable(&fixture.tfidf_vectors, &fixture.idf, std::f64::consts::E),
+            [(
+                "What is".to_string(),
+                "all of its words appear in almost every question".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn same_question_with_different_answers_is_a_contradiction() {
+        let fixture = Fixture::new(
+            &[
+                ("Can I get a refund?", "Yes, within 30 days."),
+                ("can I get a refund?", "No, all sales are final."),
+                ("What does EVA do?", "EVA verifies patient eligibility."),
+            ],
+            Config::default(),
+        );
+        let contradictions = find_contradictions(
+            &fixture.qa_data,
+            &fixture.tfidf_vectors,
+            &fixture.config.tokenizer,
+            0.7,
+            0.3,
+        );
+
+        assert_eq!(contradictions.len(), 1);
+        let candidate = &contradictions[0];
+        assert_eq!(
+            (candidate.q1.as_str(), candidate.q2.as_str()),
+            ("Can I get a refund?", "can I get a refund?")
+        );
+        assert!((candidate.question_similarity - 1.0).abs() < 1e-9);
+        assert_eq!(candidate.answer_similarity, 0.0);
+    }
+
+    #[test]
+    fn vocabulary_is_capped() {
+        let mut fixture = Fixture::new(&PAIRS, Config::default());
+        let vocabulary_size = fixture.idf.len();
+        let dropped = truncate_vocabulary(
+            &mut fixture.tfidf_vectors,
+            &mut fixture.idf,
+            5,
+            VocabularyRank::Idf,
+        );
+
+        assert_eq!(dropped, vocabulary_size - 5);
+        assert_eq!(fixture.idf.len(), 5);
+        for vector in fixture.tfidf_vectors.values() {
+            assert!(vector.len() <= 5);
+            assert!(vector.keys().all(|word| fixture.idf.contains_key(word)));
+        }
+    }
+
+    #[test]
+    fn capped_term_frequency_keeps_other_words_weighted() {
+        let fixture = Fixture::new(&PAIRS, Config::default());
+        let options = &fixture.config.tokenizer;
+        let query = "refund refund refund policy?";
+
+        let uncapped = compute_input_vector(query, &fixture.idf, tokenize, options, None);
+        assert!((uncapped["refund"] - 3.0 * uncapped["policy?"]).abs() < 1e-12);
+        let capped = compute_input_vector(query, &fixture.idf, tokenize, options, Some(1));
+        assert_eq!(capped["refund"], capped["policy?"]);
+        assert!(capped["policy?"] > uncapped["policy?"]);
+    }
+
+    #[test]
+    fn near_duplicates_are_each_others_nearest_question() {
+        let fixture = Fixture::new(&PAIRS, Config::default());
+        let nearest = |question: &str| {
+            nearest_questions(
+                &fixture.tfidf_vectors,
+                &fixture.idf,
+                &fixture.config.tokenizer,
+                question,
+                1,
+            )[0]
+            .0
+            .clone()
+        };
+
+        assert_eq!(nearest("What does EVA do?"), "What does CAM do?");
+        assert_eq!(nearest("What does CAM do?"), "What does EVA do?");
+    }
+
+    #[test]
+    fn repeated_answers_lower_the_diversity_score() {
+        let diversity = |pairs: &[(&str, &str)]| {
+            compute_answer_diversity_score(
+                &qa(pairs),
+                &HashMap::new(),
+                &TokenizerOptions::default(),
+            )
+        };
+        let repeated = diversity(&[
+            ("How do refunds work?", "Refunds take 30 days."),
+            ("When do I get my money back?", "Refunds take 30 days."),
+            ("What does EVA do?", "EVA verifies patient eligibility."),
+        ]);
+        let distinct = diversity(&[
+            ("How do refunds work?", "Refunds take 30 days."),
+            ("What does CAM do?", "CAM processes claims."),
+            ("What does EVA do?", "EVA verifies patient eligibility."),
+        ]);
+
+        assert!((repeated - 2.0 / 3.0).abs() < 1e-9);
+        assert!((distinct - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn questions_containing_a_word_are_listed() {
+        let qa_data = qa(&PAIRS);
+        let options = TokenizerOptions::default();
+
+        assert_eq!(
+            questions_containing_word(&qa_data, &options, "DOES"),
+            [
+                "How does PHIL work?",
+                "What does CAM do?",
+                "What does EVA do?"
+            ]
+        );
+        assert!(questions_containing_word(&qa_data, &options, "invoice").is_empty());
+    }
+
+    #[test]
+    fn expanded_contractions_match_their_full_words() {
+        let pairs = [
+            ("What cannot EVA do?", "EVA does not process claims."),
+            ("What does CAM do?", "CAM processes claims."),
+            ("How does PHIL work?", "PHIL posts payments."),
+        ];
+        let fixture = Fixture::new(
+            &pairs,
+            Config {
+                tokenizer: TokenizerOptions {
+                    expand_contractions: true,
+                    ..TokenizerOptions::default()
+                },
+                ..Config::default()
+            },
+        );
+
+        let question = "What cannot EVA do?".to_string();
+        assert!((fixture.scores("what can't EVA do?")[&question] - 1.0).abs() < 1e-9);
+        assert_eq!(
+            fixture.answer("what can't EVA do?"),
+            "EVA does not process claims."
+        );
+    }
+
+    #[test]
+    fn answers_in_another_language_are_withheld() {
+        let pairs = [
+            (
+                "What does EVA do?",
+                "EVA verifies patient eligibility and benefits information in real time, eliminating manual data entry errors.",
+            ),
+            (
+                "What does CAM do?",
+                "CAM procesa las reclamaciones de seguros médicos de forma automática y rápida, reduciendo los errores manuales.",
+            ),
+            ("How does PHIL work?", "PHIL posts payments."),
+        ];
+        let fixture = Fixture::new(
+            &pairs,
+            Config {
+                answer_language: Some(Lang::Eng),
+                ..Config::default()
+            },
+        );
+
+        assert_eq!(fixture.answer("what does eva do?"), pairs[0].1);
+        assert_eq!(
+            fixture.answer("what does cam do?"),
+            "I'm sorry, I don't have an answer to that in English."
+        );
+    }
+
+    #[test]
+    fn suggestions_list_prefix_matches_first() {
+        let mut qa_data = qa(&PAIRS);
+        qa_data.insert("Refund timing?".to_string(), "30 days.".to_string());
+
+        assert_eq!(
+            suggest(&qa_data, "REFUND", 5),
+            ["Refund timing?", "What is the refund policy?"]
+        );
+        assert_eq!(
+            suggest(&qa_data, "what", 2),
+            ["What does CAM do?", "What does EVA do?"]
+        );
+        assert!(suggest(&qa_data, "invoice", 5).is_empty());
+    }
+
+    #[test]
+    fn query_coverage_is_the_share_of_indexed_terms() {
+        let fixture = Fixture::new(&PAIRS, Config::default());
+        let vocabulary: HashSet<String> = [
+            "what", "does", "eva", "cam", "phil", "refund", "reset", "invoice", "billing",
+            "pricing",
+        ]
+        .map(String::from)
+        .into();
+
+        assert!((compute_query_coverage(&fixture.idf, &vocabulary) - 0.7).abs() < 1e-12);
+        assert_eq!(compute_query_coverage(&fixture.idf, &HashSet::new()), 0.0);
+    }
+
+    #[test]
+    fn emphasized_words_decide_the_match() {
+        let fixture = Fixture::new(
+            &[
+                ("EVA pricing", "EVA costs $100 a month."),
+                ("CAM billing", "CAM is billed per claim."),
+                ("PHIL support", "PHIL is supported around the clock."),
+            ],
+            Config::default(),
+        );
+
+        let scores = fixture.scores("eva billing");
+        assert_eq!(
+            scores[&"EVA pricing".to_string()],
+            scores[&"CAM billing".to_string()]
+        );
+        assert_eq!(fixture.answer("*eva* billing"), "EVA costs $100 a month.");
+        assert_eq!(fixture.answer("eva *billing*"), "CAM is billed per claim.");
+    }
+
+    #[test]
+    fn self_test_flags_ambiguous_questions() {
+        let fixture = Fixture::new(
+            &[
+                ("What is EVA?", "EVA verifies patient eligibility."),
+                ("what is eva?", "EVA is our eligibility agent."),
+                ("How does PHIL work?", "PHIL posts payments."),
+            ],
+            Config::default(),
+        );
+        let failures = self_test(
+            &fixture.qa_data,
+            &fixture.tfidf_vectors,
+            &fixture.idf,
+            None,
+            None,
+            &fixture.config,
+        );
+
+        // The two phrasings tie, so whichever wins, the other resolves to it
+        assert_eq!(failures.len(), 1);
+        let pair = ["What is EVA?", "what is eva?"];
+        assert!(pair.contains(&failures[0].question.as_str()));
+        let matched = failures[0].matched.as_deref().unwrap();
+        assert!(pair.contains(&matched) && matched != failures[0].question);
+    }
+
+    #[test]
+    fn vector_export_round_trip_answers_like_the_original() {
+        let mut qa_data = qa(&PAIRS);
+        let aliases = insert_aliases(
+            &mut qa_data,
+            vec![(
+                "Who checks insurance coverage?".to_string(),
+                "What does EVA do?".to_string(),
+            )],
+        )
+        .unwrap();
+        let original = Fixture::from_corpus((qa_data, aliases), Config::default());
+        let mut buffer = Vec::new();
+        to_jsonl_stream_with_vectors(
+            &original.qa_data,
+            &original.aliases,
+            &original.tfidf_vectors,
+            &original.idf,
+            &mut buffer,
+        )
+        .unwrap();
+
+        let (corpus, index) = from_jsonl_stream(&buffer[..], true).unwrap();
+        let (tfidf_vectors, idf) = index.unwrap();
+        let close = |a: &HashMap<String, f64>, b: &HashMap<String, f64>| {
+            a.len() == b.len()
+                && a.iter()
+                    .all(|(word, value)| (b[word] - value).abs() < 1e-12)
+        };
+        assert!(close(&idf, &original.idf));
+        assert_eq!(tfidf_vectors.len(), original.tfidf_vectors.len());
+        for (question, vector) in &original.tfidf_vectors {
+            assert!(close(&tfidf_vectors[question], vector), "{}", question);
+        }
+
+        let restored = Fixture::with_index(corpus, (tfidf_vectors, idf), Config::default());
+        for query in [
+            "what does eva do",
+            "who checks insurance coverage?",
+            "cam",
+            "how does phil work",
+            "weather",
+        ] {
+            assert_eq!(restored.answer(query), original.answer(query));
+        }
+    }
+
+    #[test]
+    fn vector_import_needs_every_vector() {
+        let lines = concat!(
+            r#"{"idf": {"eva": 0.69, "cam": 0.69}}"#,
+            "\n",
+            r#"{"question": "eva", "answer": "EVA verifies.", "vector": {"eva": 0.69}}"#,
+            "\n",
+            r#"{"question": "cam", "answer": "CAM processes claims."}"#,
+        );
+        let err = from_jsonl_stream(lines.as_bytes(), false).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "'cam' has no \"vector\", though the file has an \"idf\" table"
+        );
+
+        // Without the IDF table, vectors are not an index of their own
+        let (_, index) = from_jsonl_stream(
+            lines
+                .lines()
+                .skip(1)
+                .collect::<Vec<_>>()
+                .join("\n")
+                .as_bytes(),
+            false,
+        )
+        .unwrap();
+        assert!(index.is_none());
+    }
+
+    #[test]
+    fn keyword_fallback_finds_words_in_answers() {
+        let fixture = Fixture::new(
+            &PAIRS,
+            Config {
+                keyword_fallback: true,
+                ..Config::default()
+            },
+        );
+
+        assert_eq!(
+            fixture.answer("patient eligibility"),
+            "I couldn't find a close match, but this entry mentions some of your keywords (keyword match, not a semantic match):\n\
+             Q: What does EVA do?\nA: EVA verifies patient eligibility."
+        );
+    }
+
+    #[test]
+    fn corpus_summary_matches_a_hand_count() {
+        let fixture = Fixture::new(
+            &[
+                ("What does EVA do?", "EVA verifies eligibility."),
+                ("What does CAM do?", "CAM processes claims."),
+                ("How does PHIL post payments?", "Automatically."),
+            ],
+            Config::default(),
+        );
+        let summary = summarize_corpus(
+            &fixture.qa_data,
+            &fixture.aliases,
+            &fixture.idf,
+            &fixture.config.tokenizer,
+        );
+
+        assert_eq!(summary.total_pairs, 3);
+        assert_eq!(summary.vocabulary_size, 9);
+        assert!((summary.avg_question_tokens - 13.0 / 3.0).abs() < 1e-12);
+        assert!((summary.avg_answer_chars - 20.0).abs() < 1e-12);
+        assert_eq!(
+            summary.question_token_percentiles,
+            [(50, 4), (90, 5), (99, 5)]
+        );
+        assert_eq!(
+            summary.answer_char_percentiles,
+            [(50, 21), (90, 25), (99, 25)]
+        );
+        let top_terms: Vec<&str> = summary.top_terms.iter().map(|(t, _)| t.as_str()).collect();
+        assert_eq!(
+            top_terms,
+            [
+                "does",
+                "do?",
+                "what",
+                "cam",
+                "eva",
+                "how",
+                "payments?",
+                "phil",
+                "post"
+            ]
+        );
+        assert_eq!(summary.top_terms[0].1, 0.0);
+        assert!((summary.top_terms[1].1 - 1.5_f64.ln()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn similarity_matrix_is_square_and_symmetric() {
+        let fixture = Fixture::new(&PAIRS, Config::default());
+        let (questions, matrix) = cosine_similarity_matrix(&fixture.tfidf_vectors);
+
+        assert_eq!(questions.len(), PAIRS.len());
+        assert_eq!(matrix.len(), PAIRS.len());
+        for (i, row) in matrix.iter().enumerate() {
+            assert_eq!(row.len(), PAIRS.len());
+            assert_eq!(row[i], 1.0);
+            for (j, similarity) in row.iter().enumerate() {
+                assert_eq!(*similarity, matrix[j][i]);
+                let expected = cosine_similarity(
+                    &fixture.tfidf_vectors[questions[i]],
+                    &fixture.tfidf_vectors[questions[j]],
+                );
+                assert!((similarity - expected).abs() < 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn capped_vocabulary_still_matches_retained_terms() {
+        let mut fixture = Fixture::new(&PAIRS, Config::default());
+        truncate_vocabulary(
+            &mut fixture.tfidf_vectors,
+            &mut fixture.idf,
+            3,
+            VocabularyRank::Idf,
+        );
+
+        // The rarest terms, ties broken alphabetically
+        let mut retained: Vec<&String> = fixture.idf.keys().collect();
+        retained.sort();
+        assert_eq!(retained, ["cam", "do", "eva"]);
+        assert_eq!(fixture.tfidf_vectors["What does CAM do?"].len(), 1);
+        assert!(fixture.tfidf_vectors["How does PHIL work?"].is_empty());
+        assert_eq!(fixture.answer("what is cam"), "CAM processes claims.");
+
+        let mut by_frequency = Fixture::new(&PAIRS, Config::default());
+        truncate_vocabulary(
+            &mut by_frequency.tfidf_vectors,
+            &mut by_frequency.idf,
+            2,
+            VocabularyRank::DocumentFrequency,
+        );
+        let mut retained: Vec<&String> = by_frequency.idf.keys().collect();
+        retained.sort();
+        assert_eq!(retained, ["does", "what"]);
+    }
+
+    #[test]
+    fn in_order_overlap_tips_the_ranking() {
+        let pairs = [
+            (
+                "how do i reset my password",
+                "Use the 'Forgot password' link.",
+            ),
+            ("password reset failed", "Contact support."),
+            ("what does eva do", "EVA verifies patient eligibility."),
+            ("how does phil work", "PHIL posts payments."),
+        ];
+        let plain = Fixture::new(&pairs, Config::default());
+        let ordered = Fixture::new(
+            &pairs,
+            Config {
+                subsequence_weight: 0.5,
+                ..Config::default()
+            },
+        );
+
+        assert_eq!(plain.answer("reset password"), "Contact support.");
+        assert_eq!(
+            ordered.answer("reset password"),
+            "Use the 'Forgot password' link."
+        );
+    }
+
+    #[test]
+    fn terms_in_few_questions_have_low_coverage() {
+        // "refund" and "billing" are each in half of the 12 questions, "eva" in only one
+        let qa_data: HashMap<String, String> = (0..12)
+            .map(|i| {
+                let topic = if i < 6 { "refund" } else { "billing" };
+                let eva = if i == 0 { " eva" } else { "" };
+                (format!("{} case{}{}", topic, i, eva), "Answer.".to_string())
+            })
+            .collect();
+        let fixture = Fixture::from_corpus((qa_data, HashMap::new()), Config::default());
+        let terms = find_low_coverage_terms(&fixture.tfidf_vectors, 0.1);
+
+        assert!(terms.contains(&("eva".to_string(), 1)));
+        assert!(terms
+            .iter()
+            .all(|(term, _)| term != "refund" && term != "billing"));
+        assert!(terms.iter().all(|(_, count)| *count == 1));
+        assert_eq!(terms.len(), 13);
+    }
+
+    #[test]
+    fn cross_validation_counts_paraphrases_found() {
+        let qa_data = qa(&[
+            ("cam claims", "CAM processes claims."),
+            ("cam claims status", "CAM processes claims."),
+            ("eva eligibility", "EVA verifies patient eligibility."),
+            ("eva eligibility check", "EVA verifies patient eligibility."),
+            ("phil payments", "PHIL posts payments."),
+            (
+                "what is the refund policy",
+                "Refunds are issued within 30 days.",
+            ),
+        ]);
+        let report = cross_validate(&qa_data, 3, &Config::default());
+
+        // Sorted questions go to folds in turn, so each fold holds out questions i and i + 3:
+        // both CAM questions and both EVA questions find their paraphrase, while PHIL and the refund
+        // policy have none to find
+        assert_eq!(report.folds, 3);
+        assert_eq!(report.per_fold_scores, [1.0, 0.5, 0.5]);
+        assert!((report.mean_precision_at_1 - 2.0 / 3.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn displayed_scores_never_exceed_one() {
+        assert_eq!(format!("{:.3}", display_score(1.0004)), "1.000");
+        assert_eq!(format!("{:.3}", display_score(1.0 + 1e-12)), "1.000");
+        assert_eq!(format!("{:.3}", display_score(-0.0004)), "0.000");
+
+        let question = "What does EVA do?".to_string();
+        let result = QueryResult {
+            query: "eva",
+            answer: "EVA verifies patient eligibility.".to_string(),
+            matched_question: Some(&question),
+            score: Some(display_score(1.0004)),
+        };
+        assert!(format_batch_result(&result, OutputFormat::Csv).ends_with(",1.000"));
+    }
+
+    #[test]
+    fn answer_gaps_are_the_terms_no_question_has() {
+        let fixture = Fixture::new(&PAIRS, Config::default());
+        let gaps = find_answer_gaps(
+            &fixture.idf,
+            &fixture.config.tokenizer,
+            &["EVA", "invoice", "refund", "Reset", "pricing"],
+        );
+
+        assert_eq!(gaps, ["invoice", "pricing"]);
+    }
+
+    #[test]
+    fn strict_parsing_rejects_misspelled_fields() {
+        let path = temp_file(
+            "misspelled.json",
+            br#"{"questions": [
+                {"question": "What does EVA do?", "answer": "EVA verifies patient eligibility."},
+                {"question": "What does CAM do?", "anser": "CAM processes claims."}
+            ]}"#,
+        );
+
+        let ((qa_data, _), _) = initialize_qa_data(&path, &RetryPolicy::default(), false).unwrap();
+        assert_eq!(
+            qa_data,
+            qa(&[("What does EVA do?", "EVA verifies patient eligibility.")])
+        );
+        let err = initialize_qa_data(&path, &RetryPolicy::default(), true).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "entry 2: unknown field 'anser' (expected \"question\", \"answer\" and optional \"aliases\")"
+        );
+    }
+
+    #[test]
+    fn long_questions_split_into_sentences() {
+        let long = "I was charged twice for my subscription. \
+                    How do I get a refund for the second charge on my card?";
+        let mut qa_data = qa(&[
+            (long, "Refunds are issued within 30 days."),
+            ("What does EVA do?", "EVA verifies patient eligibility."),
+        ]);
+        assert_eq!(tokenize(long, &TokenizerOptions::default()).len(), 20);
+        let report = split_long_questions(
+            &mut qa_data,
+            &mut HashMap::new(),
+            &TokenizerOptions::default(),
+            12,
+        );
+
+        assert_eq!(report.split_count, 1);
+        assert_eq!(report.new_entries.len(), 2);
+        assert_eq!(
+            qa_data,
+            qa(&[
+                (
+                    "I was charged twice for my subscription.",
+                    "Refunds are issued within 30 days."
+                ),
+                (
+                    "How do I get a refund for the second charge on my card?",
+                    "Refunds are issued within 30 days."
+                ),
+                ("What does EVA do?", "EVA verifies patient eligibility."),
+            ])
+        );
+    }
+
+    #[test]
+    fn most_similar_pair_matches_a_hand_calculation() {
+        let fixture = Fixture::new(
+            &[
+                ("eva pricing", "EVA costs $100 a month."),
+                ("eva pricing plans", "Basic and Pro."),
+                ("cam billing", "CAM is billed per claim."),
+            ],
+            Config::default(),
+        );
+        let (questions, matrix) = cosine_similarity_matrix(&fixture.tfidf_vectors);
+        let pairs = most_similar_pairs(&questions, &matrix, 1);
+
+        // "eva" and "pricing" weigh ln(3/2) in both questions, and "plans" weighs ln(3) in the second
+        let (shared, plans) = (1.5_f64.ln(), 3_f64.ln());
+        let expected = 2_f64.sqrt() * shared / (2.0 * shared * shared + plans * plans).sqrt();
+        assert_eq!(pairs.len(), 1);
+        assert_eq!(
+            (pairs[0].0.as_str(), pairs[0].1.as_str()),
+            ("eva pricing", "eva pricing plans")
+        );
+        assert!((pairs[0].2 - expected).abs() < 1e-12);
+    }
+
+    #[test]
+    fn a_single_question_can_be_matched() {
+        let fixture = Fixture::new(
+            &[("What does EVA do?", "EVA verifies patient eligibility.")],
+            Config::default(),
+        );
+
+        assert!(fixture.idf.values().all(|value| *value == 1.0));
+        assert_eq!(
+            fixture.answer("what does eva do?"),
+            "EVA verifies patient eligibility."
+        );
+    }
+
+    #[test]
+    fn related_questions_are_ranked_by_similarity() {
+        let fixture = Fixture::new(&PAIRS, Config::default());
+        let related = nearest_to_question(&fixture.tfidf_vectors, "What does EVA do?", 4).unwrap();
+
+        let questions: Vec<&str> = related.iter().map(|(q, _)| q.as_str()).collect();
+        assert_eq!(
+            questions,
+            [
+                "What does CAM do?",
+                "How does PHIL work?",
+                "What is the refund policy?",
+                "How do I reset my password?"
+            ]
+        );
+        assert!(related.windows(2).all(|pair| pair[0].1 > pair[1].1));
+        assert!(nearest_to_question(&fixture.tfidf_vectors, "what does eva do", 4).is_none());
+    }
+
+    #[test]
+    fn answer_template_applies_to_every_answer() {
+        let fixture = Fixture::new(
+            &PAIRS,
+            Config {
+                answer_template: Some("{answer}\n-- Thoughtful AI".to_string()),
+                ..Config::default()
+            },
+        );
+
+        assert_eq!(
+            fixture.answer("what does eva do"),
+            "EVA verifies patient eligibility.\n-- Thoughtful AI"
+        );
+        assert_eq!(
+            fixture.answer("how does phil work"),
+            "PHIL posts payments.\n-- Thoughtful AI"
+        );
+    }
+
+    #[test]
+    fn questions_equal_once_normalized_are_flagged() {
+        let mut qa_data = qa(&PAIRS);
+        qa_data.insert("What is EVA?".to_string(), "EVA verifies.".to_string());
+        qa_data.insert("what is eva".to_string(), "EVA verifies.".to_string());
+
+        assert_eq!(
+            find_normalized_duplicates(&qa_data, &HashMap::new(), &TokenizerOptions::default()),
+            [["What is EVA?", "what is eva"]]
+        );
+
+        // An alias spelled like its own question is not a duplicate
+        let aliases = qa(&[("what is eva", "What is EVA?")]);
+        assert!(
+            find_normalized_duplicates(&qa_data, &aliases, &TokenizerOptions::default()).is_empty()
+        );
+    }
+
+    #[test]
+    fn diversified_suggestions_surface_distinct_answers() {
+        let refunds = "Refunds are issued within 30 days.";
+        let pairs = [
+            ("how do refunds work", refunds),
+            ("how do refunds work exactly", refunds),
+            ("how do refunds work here", refunds),
+            (
+                "refunds for annual plans",
+                "Annual plans are refunded pro rata.",
+            ),
+            ("what does eva do", "EVA verifies patient eligibility."),
+            ("how does phil work", "PHIL posts payments."),
+        ];
+        let suggestions = |diversify_suggestions| {
+            Fixture::new(
+                &pairs,
+                Config {
+                    suggestions: 2,
+                    diversify_suggestions,
+                    ..Config::default()
+                },
+            )
+            .answer("refunds")
+        };
+
+        assert!(suggestions(false)
+            .contains("  1. how do refunds work\n  2. how do refunds work exactly\n"));
+        assert!(
+            suggestions(true).contains("  1. how do refunds work\n  2. refunds for annual plans\n")
+        );
+    }
+
+    #[test]
+    fn questions_of_common_words_get_improvements() {
+        let fixture = Fixture::new(&PAIRS, Config::default());
+        let variants =
+            suggest_question_improvements(&fixture.idf, &fixture.config.tokenizer, "what does");
+
+        assert_eq!(variants.len(), 2);
+        for variant in &variants {
+            assert_ne!(variant, "what does");
+            assert_eq!(variant.split_whitespace().count(), 2);
+        }
+    }
+
+    #[test]
+    fn byte_order_mark_is_skipped_and_other_encodings_rejected() {
+        let path = temp_file(
+            "bom.json",
+            b"\xEF\xBB\xBF{\"questions\": [{\"question\": \"What does EVA do?\", \"answer\": \"EVA verifies patient eligibility.\"}]}",
+        );
+        let ((qa_data, _), _) = initialize_qa_data(&path, &RetryPolicy::default(), false).unwrap();
+        assert_eq!(
+            qa_data,
+            qa(&[("What does EVA do?", "EVA verifies patient eligibility.")])
+        );
+
+        // "¿Qué hace EVA?" saved as Latin-1
+        let path = temp_file(
+            "latin1.json",
+            b"{\"questions\": [{\"question\": \"\xBFQu\xE9 hace EVA?\", \"answer\": \"EVA\"}]}",
+        );
+        let err = initialize_qa_data(&path, &RetryPolicy::default(), false).unwrap_err();
+        assert!(err.to_string().starts_with("not valid UTF-8"));
+    }
+
+    #[test]
+    fn answer_only_terms_clear_the_default_threshold() {
+        let indexed = Fixture::new(
+            &PAIRS,
+            Config {
+                index_answers: true,
+                ..Config::default()
+            },
+        );
+        let patient = indexed.scores("patient")[&"What does EVA do?".to_string()];
+        assert!(patient > indexed.config.threshold, "score {}", patient);
+        assert_eq!(
+            indexed.answer("patient"),
+            "EVA verifies patient eligibility."
+        );
+
+        let plain = Fixture::new(&PAIRS, Config::default());
+        assert!(plain.answer("patient").starts_with("I'm sorry"));
+    }
+
+    #[test]
+    fn logged_queries_are_redacted_but_answered_in_full() {
+        let pairs = [
+            ("Who is jane@example.com?", "Jane runs billing support."),
+            ("What does EVA do?", "EVA verifies patient eligibility."),
+            ("How does PHIL work?", "PHIL posts payments."),
+        ];
+        let fixture = Fixture::new(
+            &pairs,
+            Config {
+                redact_patterns: vec![regex::Regex::new(r"\S+@\S+\.\w+").unwrap()],
+                ..Config::default()
+            },
+        );
+        let mut session = fixture.session();
+        session.handle_line("who is jane@example.com?");
+
+        assert_eq!(
+            session.history,
+            [(
+                "who is [REDACTED]?".to_string(),
+                "Jane runs billing support.".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn batch_formats_escape_commas_and_quotes() {
+        let question = "Does CAM handle denials?".to_string();
+        let result = QueryResult {
+            query: "cam, denials",
+            answer: "Yes, it \"reworks\" them.\tSee\nthe guide".to_string(),
+            matched_question: Some(&question),
+            score: Some(0.75),
+        };
+
+        assert_eq!(
+            format_batch_result(&result, OutputFormat::Csv),
+            "\"cam, denials\",\"Yes, it \"\"reworks\"\" them.\tSee\nthe guide\",Does CAM handle denials?,0.750"
+        );
+        assert_eq!(
+            format_batch_result(&result, OutputFormat::Tsv),
+            "cam, denials\tYes, it \"reworks\" them.\\tSee\\nthe guide\tDoes CAM handle denials?\t0.750"
+        );
+        let line: Value =
+            serde_json::from_str(&format_batch_result(&result, OutputFormat::Jsonl)).unwrap();
+        assert_eq!(
+            line,
+            json!({
+                "query": "cam, denials",
+                "answer": "Yes, it \"reworks\" them.\tSee\nthe guide",
+                "matched_question": "Does CAM handle denials?",
+                "score": 0.75,
+            })
+        );
+    }
+
+    #[test]
+    fn aliases_answer_like_their_question_and_count_once() {
+        let path = temp_file(
+            "aliases.json",
+            br#"{"questions": [
+                {"question": "What does EVA do?", "answer": "EVA verifies patient eligibility.",
+                 "aliases": ["Who checks insurance coverage?", "What does EVA do?"]},
+                {"question": "What does CAM do?", "answer": "CAM processes claims."},
+                {"question": "How does PHIL work?", "answer": "PHIL posts payments."}
+            ]}"#,
+        );
+        let ((mut qa_data, mut aliases), _) =
+            initialize_qa_data(&path, &RetryPolicy::default(), false).unwrap();
+        assert_eq!(
+            aliases,
+            qa(&[("Who checks insurance coverage?", "What does EVA do?")])
+        );
+
+        // The alias shares its question's answer, so it is not a duplicate to remove
+        let report = deduplicate_answers(&mut qa_data, &mut aliases);
+        assert_eq!(report.removed_count, 0);
+
+        let fixture = Fixture::from_corpus((qa_data, aliases), Config::default());
+        let summary = summarize_corpus(
+            &fixture.qa_data,
+            &fixture.aliases,
+            &fixture.idf,
+            &fixture.config.tokenizer,
+        );
+        assert_eq!(summary.total_pairs, 3);
+        assert_eq!(
+            fixture.answer("who checks insurance coverage?"),
+            "EVA verifies patient eligibility."
+        );
+        assert_eq!(
+            fixture.answer("what does eva do?"),
+            "EVA verifies patient eligibility."
+        );
+    }
+
+    #[test]
+    fn colliding_aliases_are_load_errors() {
+        let path = temp_file(
+            "alias-collision.json",
+            br#"{"questions": [
+                {"question": "What does EVA do?", "answer": "EVA verifies patient eligibility.",
+                 "aliases": ["What does CAM do?"]},
+                {"question": "What does CAM do?", "answer": "CAM processes claims."}
+            ]}"#,
+        );
+        let err = initialize_qa_data(&path, &RetryPolicy::default(), false).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "alias 'What does CAM do?' of 'What does EVA do?' is already a question"
+        );
+    }
+
+    #[test]
+    fn rarest_terms_are_the_most_important() {
+        let fixture = Fixture::new(&PAIRS, Config::default());
+        let importance =
+            compute_term_importance(&fixture.idf, &fixture.config, "what does cam do? today");
+        let terms: Vec<&str> = importance.iter().map(|(term, _)| term.as_str()).collect();
+
+        assert_eq!(terms, ["cam", "do?", "does", "what", "today"]);
+        assert!(importance.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+        assert_eq!(importance[4].1, 0.0);
+    }
+
+    #[test]
+    fn answer_links_expand_and_stop_at_cycles() {
+        let qa_data = qa(&[
+            ("Refund policy", "Refunds are issued within 30 days."),
+            ("Cancel plan", "Cancel anytime. [[Refund policy]]"),
+            ("Loop", "See [[Loop]] and [[Missing]]."),
+            ("Ping", "Ping, [[Pong]]"),
+            ("Pong", "pong, [[Ping]]"),
+        ]);
+        let expand =
+            |question: &str| resolve_links(&qa_data, &qa_data[question], &mut vec![question]);
+
+        assert_eq!(
+            expand("Cancel plan"),
+            "Cancel anytime. Refunds are issued within 30 days."
+        );
+        assert_eq!(expand("Loop"), "See \"Loop\" and \"Missing\".");
+        assert_eq!(expand("Ping"), "Ping, pong, \"Ping\"");
+    }
+
+    #[test]
+    fn base_two_idf_is_the_natural_idf_over_ln_two() {
+        let natural = Fixture::new(&PAIRS, Config::default());
+        let mut base_two = Fixture::new(&PAIRS, Config::default());
+        change_idf_log_base(&mut base_two.tfidf_vectors, &mut base_two.idf, 2.0);
+
+        for (word, value) in &natural.idf {
+            assert!((base_two.idf[word] - value / 2f64.ln()).abs() < 1e-12);
+        }
+        for (question, vector) in &natural.tfidf_vectors {
+            for (word, weight) in vector {
+                let converted = base_two.tfidf_vectors[question][word];
+                assert!((converted - weight / 2f64.ln()).abs() < 1e-12);
+            }
+        }
+        assert_eq!(
+            natural.answer("what does cam do?"),
+            base_two.answer("what does cam do?")
+        );
+    }
+
+    #[test]
+    fn unmatchable_threshold_follows_the_log_base() {
+        // "help" is in 24 of the 25 questions, for an IDF of ln(25/24) = 0.041, or 0.059 in base 2
+        let mut qa_data = qa(&[("help", "Ask away."), ("what does eva do", "EVA verifies.")]);
+        for topic in 1..=23 {
+            qa_data.insert(
+                format!("help topic{}", topic),
+                "Read the guide.".to_string(),
+            );
+        }
+        let mut fixture = Fixture::from_corpus((qa_data, HashMap::new()), Config::default());
+        let flagged = |fixture: &Fixture, base| -> Vec<String> {
+            find_unmatchable(&fixture.tfidf_vectors, &fixture.idf, base)
+                .into_iter()
+                .map(|(question, _)| question)
+                .collect()
+        };
+
+        assert_eq!(flagged(&fixture, std::f64::consts::E), ["help"]);
+        change_idf_log_base(&mut fixture.tfidf_vectors, &mut fixture.idf, 2.0);
+        assert_eq!(flagged(&fixture, 2.0), ["help"]);
+    }
+
+    #[test]
+    fn why_lists_shared_and_missing_tokens() {
+        let fixture = Fixture::new(&PAIRS, Config::default());
+        let explanation = explain_match(
+            &fixture.tfidf_vectors,
+            &fixture.idf,
+            &fixture.config,
+            "how does cam work? today",
+            "How does PHIL work?",
+        )
+        .unwrap();
+
+        assert_eq!(explanation.shared, ["does", "how", "work?"]);
+        assert_eq!(explanation.query_only, ["cam", "today"]);
+        assert_eq!(explanation.question_only, ["phil"]);
+        assert!(explanation.similarity > 0.0 && explanation.similarity < 1.0);
+        assert!(explain_match(
+            &fixture.tfidf_vectors,
+            &fixture.idf,
+            &fixture.config,
+            "how does cam work?",
+            "How does CAM work?",
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn quoted_args_split_on_quotes_only() {
+        assert_eq!(
+            parse_quoted_args(r#" "how does cam work" "How does PHIL work?" "#),
+            Some(vec![
+                "how does cam work".to_string(),
+                "How does PHIL work?".to_string()
+            ])
+        );
+        assert_eq!(parse_quoted_args(r#""open"#), None);
+        assert_eq!(parse_quoted_args(r#""one" two"#), None);
+    }
+
+    #[test]
+    fn echoed_answers_include_the_matched_question() {
+        let echo = Fixture::new(
+            &PAIRS,
+            Config {
+                echo_question: true,
+                ..Config::default()
+            },
+        );
+
+        assert_eq!(
+            echo.answer("what does cam do?"),
+            "Q: What does CAM do?\nA: CAM processes claims."
+        );
+        assert_eq!(
+            Fixture::new(&PAIRS, Config::default()).answer("what does cam do?"),
+            "CAM processes claims."
+        );
+
+        let mut qa_data = qa(&PAIRS);
+        let aliases = insert_aliases(
+            &mut qa_data,
+            vec![(
+                "Who handles claims?".to_string(),
+                "What does CAM do?".to_string(),
+            )],
+        )
+        .unwrap();
+        let echo = Fixture::from_corpus(
+            (qa_data, aliases),
+            Config {
+                echo_question: true,
+                ..Config::default()
+            },
+        );
+        assert_eq!(
+            echo.answer("who handles claims?"),
+            "Q: What does CAM do?\nA: CAM processes claims."
+        );
+    }
+
+    #[test]
+    fn answer_searches_list_each_entry_once() {
+        let mut qa_data = qa(&PAIRS);
+        let aliases = insert_aliases(
+            &mut qa_data,
+            vec![(
+                "Who checks insurance coverage?".to_string(),
+                "What does EVA do?".to_string(),
+            )],
+        )
+        .unwrap();
+        let fixture = Fixture::from_corpus(
+            (qa_data, aliases),
+            Config {
+                answer_search_fallback: true,
+                ..Config::default()
+            },
+        );
+
+        assert_eq!(
+            search_answers(&fixture.qa_data, &fixture.aliases, "eligibility"),
+            [(
+                &"What does EVA do?".to_string(),
+                &"EVA verifies patient eligibility.".to_string()
+            )]
+        );
+        assert_eq!(
+            fixture.answer("eligibility"),
+            "I found this in our knowledge base:\nEVA verifies patient eligibility."
+        );
+    }
+
+    #[test]
+    fn aliases_of_a_split_question_move_to_its_first_sentence() {
+        let long = "I was charged twice for my subscription. \
+                    How do I get a refund for the second charge on my card?";
+        let mut qa_data = qa(&[
+            (long, "Refunds are issued within 30 days."),
+            ("What does EVA do?", "EVA verifies patient eligibility."),
+        ]);
+        let mut aliases = insert_aliases(
+            &mut qa_data,
+            vec![(
+                "I paid twice this month. Can I get my money back?".to_string(),
+                long.to_string(),
+            )],
+        )
+        .unwrap();
+        split_long_questions(&mut qa_data, &mut aliases, &TokenizerOptions::default(), 8);
+
+        // The alias is long too, but it is kept whole
+        assert_eq!(
+            aliases,
+            qa(&[(
+                "I paid twice this month. Can I get my money back?",
+                "I was charged twice for my subscription."
+            )])
+        );
+        assert_eq!(qa_data.len(), 4);
+    }
+
+    #[test]
+    fn matches_through_an_alias_report_its_question() {
+        let mut qa_data = qa(&PAIRS);
+        let aliases = insert_aliases(
+            &mut qa_data,
+            vec![(
+                "Who checks insurance coverage?".to_string(),
+                "What does EVA do?".to_string(),
+            )],
+        )
+        .unwrap();
+        let fixture = Fixture::from_corpus((qa_data, aliases), Config::default());
+        let session = fixture.session();
+        let result = session.answer_query("who checks insurance coverage?");
+
+        assert_eq!(result.answer, "EVA verifies patient eligibility.");
+        assert_eq!(
+            result.matched_question.map(String::as_str),
+            Some("What does EVA do?")
+        );
+    }
+
+    #[test]
+    fn misspelled_questions_array_is_an_error() {
+        let path = temp_file(
+            "questoins.json",
+            br#"{"questoins": [{"question": "What does EVA do?", "answer": "EVA verifies."}]}"#,
+        );
+        let err = initialize_qa_data(&path, &RetryPolicy::default(), true).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unknown top-level field 'questoins' (expected \"questions\")"
+        );
+        let err = initialize_qa_data(&path, &RetryPolicy::default(), false).unwrap_err();
+        assert_eq!(err.to_string(), "expected a top-level \"questions\" array");
+
+        let path = temp_file(
+            "top-level-comment.json",
+            br#"{"_comment": "billing FAQ", "questions": []}"#,
+        );
+        assert!(initialize_qa_data(&path, &RetryPolicy::default(), true).is_ok());
+    }
+
+    #[test]
+    fn diversity_command_takes_trailing_arguments() {
+        let fixture = Fixture::new(&PAIRS, Config::default());
+        let mut session = fixture.session();
+        session.handle_line("/diversity all");
+
+        // A command is not a question, so nothing is kept for the transcript
+        assert!(session.history.is_empty());
+    }
+
+    #[test]
+    fn stats_command_takes_trailing_arguments() {
+        let fixture = Fixture::new(&PAIRS, Config::default());
+        let mut session = fixture.session();
+        session.handle_line("/stats all");
+
+        // A command is not a question, so nothing is kept for the transcript
+        assert!(session.history.is_empty());
+    }
+
+    #[test]
+    fn unmatchable_command_takes_trailing_arguments() {
+        let fixture = Fixture::new(&PAIRS, Config::default());
+        let mut session = fixture.session();
+        session.handle_line("/unmatchable all");
+
+        // A command is not a question, so nothing is kept for the transcript
+        assert!(session.history.is_empty());
+    }
+
+    #[test]
+    fn vocabulary_cap_applies_to_every_index() {
+        let fixture = Fixture::new(
+            &PAIRS,
+            Config {
+                max_vocabulary_size: Some(3),
+                phonetic_weight: 0.5,
+                index_answers: true,
+                ..Config::default()
+            },
+        );
+
+        for index in [&fixture.phonetic_index, &fixture.answer_index] {
+            let (tfidf_vectors, idf) = index.as_ref().unwrap();
+            assert_eq!(idf.len(), 3);
+            assert!(tfidf_vectors
+                .values()
+                .all(|vector| vector.keys().all(|word| idf.contains_key(word))));
+        }
+    }
+
+    #[test]
+    fn fallback_answers_expand_links_too() {
+        let pairs = [
+            (
+                "What is the refund policy?",
+                "Refunds are issued within 30 days.",
+            ),
+            (
+                "How do I cancel?",
+                "Cancel anytime. [[What is the refund policy?]]",
+            ),
+            ("What does EVA do?", "EVA verifies patient eligibility."),
+        ];
+        let search = Fixture::new(
+            &pairs,
+            Config {
+                answer_search_fallback: true,
+                ..Config::default()
+            },
+        );
+        let keyword = Fixture::new(
+            &pairs,
+            Config {
+                keyword_fallback: true,
+                ..Config::default()
+            },
+        );
+
+        assert_eq!(
+            search.answer("anytime"),
+            "I found this in our knowledge base:\nCancel anytime. Refunds are issued within 30 days."
+        );
+        assert!(keyword.answer("anytime").ends_with(
+            "Q: How do I cancel?\nA: Cancel anytime. Refunds are issued within 30 days."
+        ));
+    }
 }
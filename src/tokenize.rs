@@ -0,0 +1,263 @@
+use crate::emoji::{emoji_name, is_emoji};
+use std::str::FromStr;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// How text is cut into words before any further processing
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+pub enum TokenizerStrategy {
+    /// Split on whitespace only, so punctuation stays attached to words ("refund,policy" is one token)
+    #[default]
+    Whitespace,
+    /// Split on Unicode word boundaries (UAX #29), dropping punctuation ("refund,policy" is two tokens)
+    Words,
+}
+
+impl FromStr for TokenizerStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "whitespace" => Ok(TokenizerStrategy::Whitespace),
+            "words" => Ok(TokenizerStrategy::Words),
+            _ => Err(format!("unknown tokenizer '{}'", s)),
+        }
+    }
+}
+
+/// Options changing how text is split into tokens; they apply to the corpus and queries alike
+#[derive(Default)]
+pub struct TokenizerOptions {
+    /// Where word boundaries fall
+    pub strategy: TokenizerStrategy,
+    /// Expand contractions such as "can't" and "it's" into their full words
+    pub expand_contractions: bool,
+    /// Replace common emoji with their names ("🤖" becomes "robot") and drop any other emoji
+    pub normalize_emoji: bool,
+}
+
+/// Splits text into lowercase word tokens; shared by the corpus and query sides so both are tokenized identically
+pub fn tokenize(text: &str, options: &TokenizerOptions) -> Vec<String> {
+    let mut cleaned = String::with_capacity(text.len());
+    for c in text.chars().filter(|c| !is_bidi_control(*c)) {
+        if !options.normalize_emoji {
+            cleaned.push(c);
+        } else if let Some(name) = emoji_name(c) {
+            // Pad with spaces so an emoji written right next to a word still becomes its own token
+            cleaned.push(' ');
+            cleaned.push_str(name);
+            cleaned.push(' ');
+        } else if !is_emoji(c) {
+            cleaned.push(c);
+        }
+    }
+    let text = cleaned.to_lowercase();
+
+    let words: Box<dyn Iterator<Item = &str>> = match options.strategy {
+        TokenizerStrategy::Whitespace => Box::new(text.split_whitespace()),
+        TokenizerStrategy::Words => Box::new(text.unicode_words()),
+    };
+    if options.expand_contractions {
+        words
+            .flat_map(|word| {
+                expand_contraction(word)
+                    .split_whitespace()
+                    .map(String::from)
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    } else {
+        words.map(String::from).collect()
+    }
+}
+
+/// Expands an English contraction ("don't" -> "do not", "can't" -> "cannot"); other words are returned unchanged
+/// "'s" is only expanded for pronouns and question words, since after a noun it is usually possessive
+fn expand_contraction(word: &str) -> String {
+    let word = word.replace('\u{2019}', "'");
+    match word.as_str() {
+        "can't" => return "cannot".to_string(),
+        "won't" => return "will not".to_string(),
+        "shan't" => return "shall not".to_string(),
+        "ain't" => return "is not".to_string(),
+        "let's" => return "let us".to_string(),
+        _ => {}
+    }
+
+    if let Some(stem) = word.strip_suffix("'s") {
+        if matches!(
+            stem,
+            "it" | "that" | "there" | "here" | "he" | "she" | "who" | "what" | "where" | "how"
+        ) {
+            return format!("{} is", stem);
+        }
+        return word;
+    }
+
+    const SUFFIXES: [(&str, &str); 6] = [
+        ("n't", " not"),
+        ("'re", " are"),
+        ("'ve", " have"),
+        ("'ll", " will"),
+        ("'m", " am"),
+        ("'d", " would"),
+    ];
+    for (suffix, expansion) in SUFFIXES {
+        if let Some(stem) = word.strip_suffix(suffix) {
+            if !stem.is_empty() {
+                return format!("{}{}", stem, expansion);
+            }
+        }
+    }
+    word
+}
+
+/// Removes `*word*` emphasis markers from a query
+/// Returns the query without the asterisks, along with the words that were emphasized
+pub fn strip_emphasis(input: &str) -> (String, Vec<String>) {
+    let mut emphasized = Vec::new();
+    let words: Vec<&str> = input
+        .split_whitespace()
+        .map(
+            |word| match word.strip_prefix('*').and_then(|w| w.strip_suffix('*')) {
+                Some(inner) if !inner.is_empty() => {
+                    emphasized.push(inner.to_string());
+                    inner
+                }
+                _ => word,
+            },
+        )
+        .collect();
+    (words.join(" "), emphasized)
+}
+
+/// Whether `c` is an invisible Unicode bidi control (embedding, override, isolate or direction mark)
+/// These often come along with Arabic or Hebrew text pasted from other apps and would otherwise stick to words
+fn is_bidi_control(c: char) -> bool {
+    matches!(c, '\u{200E}' | '\u{200F}' | '\u{202A}'..='\u{202E}' | '\u{2066}'..='\u{2069}')
+}
+
+/// Encodes a token with American Soundex (e.g. "robert" and "rupert" both become "R163")
+/// Returns None for tokens without any ASCII letters
+fn soundex(token: &str) -> Option<String> {
+    let digit = |c: char| match c {
+        'b' | 'f' | 'p' | 'v' => Some('1'),
+        'c' | 'g' | 'j' | 'k' | 'q' | 's' | 'x' | 'z' => Some('2'),
+        'd' | 't' => Some('3'),
+        'l' => Some('4'),
+        'm' | 'n' => Some('5'),
+        'r' => Some('6'),
+        _ => None,
+    };
+
+    let mut letters = token
+        .chars()
+        .filter(char::is_ascii_alphabetic)
+        .map(|c| c.to_ascii_lowercase());
+    let first = letters.next()?;
+
+    let mut code = first.to_ascii_uppercase().to_string();
+    let mut last = digit(first);
+    for c in letters {
+        let current = digit(c);
+        if let Some(d) = current {
+            if current != last {
+                code.push(d);
+                if code.len() == 4 {
+                    break;
+                }
+            }
+        }
+        // Vowels separate repeated codes, while 'h' and 'w' do not
+        if c != 'h' && c != 'w' {
+            last = current;
+        }
+    }
+
+    while code.len() < 4 {
+        code.push('0');
+    }
+    Some(code)
+}
+
+/// Tokenizes text and replaces every token with its Soundex code, so spelling variants that sound alike share a token
+pub fn phonetic_tokenize(text: &str, options: &TokenizerOptions) -> Vec<String> {
+    tokenize(text, options)
+        .iter()
+        .filter_map(|word| soundex(word))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn soundex_codes_names_that_sound_alike_the_same() {
+        assert_eq!(soundex("robert").as_deref(), Some("R163"));
+        assert_eq!(soundex("rupert").as_deref(), Some("R163"));
+        assert_eq!(soundex("eva"), soundex("eeva"));
+        assert_eq!(soundex("42"), None);
+    }
+
+    #[test]
+    fn bidi_controls_are_stripped_from_arabic_text() {
+        let tokens = tokenize(
+            "\u{202B}مرحبا\u{202C} \u{200F}بالعالم\u{2069}",
+            &TokenizerOptions::default(),
+        );
+        assert_eq!(tokens, ["مرحبا", "بالعالم"]);
+    }
+
+    #[test]
+    fn contractions_expand_when_enabled() {
+        let options = TokenizerOptions {
+            expand_contractions: true,
+            ..TokenizerOptions::default()
+        };
+        assert_eq!(
+            tokenize("I can't see what's wrong, don't you?", &options),
+            ["i", "cannot", "see", "what", "is", "wrong,", "do", "not", "you?"]
+        );
+        assert_eq!(tokenize("EVA's claims", &options), ["eva's", "claims"]);
+        assert_eq!(tokenize("can't", &TokenizerOptions::default()), ["can't"]);
+    }
+
+    #[test]
+    fn emphasis_markers_are_stripped() {
+        assert_eq!(
+            strip_emphasis("*refund* policy *"),
+            ("refund policy *".to_string(), vec!["refund".to_string()])
+        );
+    }
+
+    #[test]
+    fn word_boundaries_split_on_punctuation() {
+        let words = TokenizerOptions {
+            strategy: TokenizerStrategy::Words,
+            ..TokenizerOptions::default()
+        };
+        assert_eq!(tokenize("refund,policy", &words), ["refund", "policy"]);
+        assert_eq!(
+            tokenize("refund,policy", &TokenizerOptions::default()),
+            ["refund,policy"]
+        );
+    }
+
+    #[test]
+    fn emoji_are_named_when_enabled() {
+        let emoji = TokenizerOptions {
+            normalize_emoji: true,
+            ..TokenizerOptions::default()
+        };
+        assert_eq!(tokenize("🤖 automation", &emoji), ["robot", "automation"]);
+        // Unknown emoji are dropped, even when written right next to a word
+        assert_eq!(
+            tokenize("how does 🤖work🦩?", &emoji),
+            ["how", "does", "robot", "work?"]
+        );
+        assert_eq!(
+            tokenize("🤖 automation", &TokenizerOptions::default()),
+            ["🤖", "automation"]
+        );
+    }
+}
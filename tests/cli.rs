@@ -0,0 +1,361 @@
+//! End-to-end tests running the built agent
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+/// The support corpus of the unit tests, as a data file
+const DATA: &str = r#"{"questions": [
+    {"question": "What does EVA do?", "answer": "EVA verifies patient eligibility."},
+    {"question": "What does CAM do?", "answer": "CAM processes claims."},
+    {"question": "How does PHIL work?", "answer": "PHIL posts payments."},
+    {"question": "What is the refund policy?", "answer": "Refunds are issued within 30 days."},
+    {"question": "How do I reset my password?", "answer": "Use the 'Forgot password' link."}
+]}"#;
+
+/// Writes `contents` to a file named `name` in the temporary directory, unique to this test run
+fn temp_file(name: &str, contents: impl AsRef<[u8]>) -> String {
+    let path =
+        std::env::temp_dir().join(format!("thoughtful-ai-cli-{}-{}", std::process::id(), name));
+    std::fs::write(&path, contents).unwrap();
+    path.to_string_lossy().into_owned()
+}
+
+/// Runs the agent with `args`, typing `input` on its standard input
+fn run(args: &[&str], input: impl AsRef<[u8]>) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ThoughtfulAI"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // The agent may exit without reading its input, e.g. after printing a report
+    let _ = child.stdin.take().unwrap().write_all(input.as_ref());
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn idf_table_lists_terms_of_a_single_question_first() {
+    let data = temp_file("idf-table.json", DATA);
+    let output = run(&["--data", &data, "--idf-table", "1"], "");
+
+    // "cam" is in one of the five questions (ties broken alphabetically) and "what" in three
+
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        "Highest IDF (rarest terms):\n  cam                              1.6094\n\
+         Lowest IDF (most common terms):\n  what                             0.5108\n"
+    );
+}
+
+#[test]
+fn custom_prompt_is_shown_before_reading_input() {
+    let data = temp_file("prompt.json", DATA);
+    let output = run(&["--data", &data, "--prompt", "support> "], "exit\n");
+
+    assert!(stdout(&output).ends_with(
+        "support> Thank you for using the Thoughtful AI Customer Support Agent. Goodbye!\n"
+    ));
+}
+
+#[test]
+fn empty_prompt_prints_nothing() {
+    let data = temp_file("empty-prompt.json", DATA);
+    let output = run(&["--data", &data, "--prompt", ""], "exit\n");
+
+    assert!(stdout(&output).ends_with(
+        "(type 'exit' to quit):\nThank you for using the Thoughtful AI Customer Support Agent. Goodbye!\n"
+    ));
+}
+
+#[test]
+fn strict_mode_rejects_a_tiny_corpus() {
+    let data = temp_file(
+        "tiny.json",
+        r#"{"questions": [{"question": "What does EVA do?", "answer": "EVA verifies eligibility."}]}"#,
+    );
+    let lenient = run(&["--data", &data], "exit\n");
+    let strict = run(&["--data", &data, "--strict"], "exit\n");
+
+    assert!(lenient.status.success());
+    assert_eq!(strict.status.code(), Some(1));
+    assert!(
+        String::from_utf8_lossy(&strict.stderr).contains("contains only 1 questions (minimum 3)")
+    );
+    assert!(strict.stdout.is_empty());
+}
+
+#[test]
+fn input_file_is_answered_before_the_prompt() {
+    let data = temp_file("input-file.json", DATA);
+    let script = temp_file("script.txt", "what does eva do\n\nhow does phil work\n");
+    let output = run(&["--data", &data, "--input-file", &script], "exit\n");
+
+    assert_eq!(
+        stdout(&output),
+        "Welcome to the Thoughtful AI Customer Support Agent!\n\
+         Ask a question about Thoughtful AI (type 'exit' to quit):\n\
+         > what does eva do\nEVA verifies patient eligibility.\n\
+         > how does phil work\nPHIL posts payments.\n\
+         > Thank you for using the Thoughtful AI Customer Support Agent. Goodbye!\n"
+    );
+}
+
+#[test]
+fn unreadable_input_file_line_is_an_error() {
+    let data = temp_file("bad-input-file.json", DATA);
+    let script = temp_file("bad-script.txt", b"what does eva do\n\xff\xfe\n");
+    let output = run(&["--data", &data, "--input-file", &script], "exit\n");
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("could not read line 2 of"));
+}
+
+#[test]
+fn unreadable_typed_line_is_an_error() {
+    let data = temp_file("bad-stdin.json", DATA);
+    let output = run(&["--data", &data], b"what does eva do\n\xff\xfe\nexit\n");
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr.contains("could not read standard input"));
+    assert!(!stderr.contains("panicked"));
+    assert!(stdout(&output).contains("EVA verifies patient eligibility."));
+}
+
+#[test]
+fn missing_coverage_file_is_an_error() {
+    let data = temp_file("coverage.json", DATA);
+    let output = run(
+        &["--data", &data, "--coverage", "/nonexistent/queries.txt"],
+        "",
+    );
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("could not open /nonexistent/queries.txt")
+    );
+}
+
+#[test]
+fn empty_lines_are_not_answered() {
+    let data = temp_file("empty-line.json", DATA);
+    let output = run(&["--data", &data], "\n   \ncam\nexit\n");
+
+    assert_eq!(
+        stdout(&output),
+        "Welcome to the Thoughtful AI Customer Support Agent!\n\
+         Ask a question about Thoughtful AI (type 'exit' to quit):\n\
+         > > > CAM processes claims.\n\
+         > Thank you for using the Thoughtful AI Customer Support Agent. Goodbye!\n"
+    );
+}
+
+#[test]
+fn version_includes_the_package_version() {
+    let output = run(&["--version"], "");
+
+    assert!(output.status.success());
+    assert!(stdout(&output).starts_with(&format!(
+        "ThoughtfulAI {} (commit ",
+        env!("CARGO_PKG_VERSION")
+    )));
+}
+
+#[test]
+fn unwritable_export_path_is_an_error() {
+    let data = temp_file("export.json", DATA);
+    for flag in ["--export-jsonl", "--export-vectors"] {
+        let output = run(&["--data", &data, flag, "/nonexistent/out.jsonl"], "");
+
+        assert_eq!(output.status.code(), Some(1));
+        assert!(String::from_utf8_lossy(&output.stderr)
+            .contains("could not write /nonexistent/out.jsonl"));
+    }
+}
+
+#[test]
+fn exported_vectors_load_back_as_they_are() {
+    let data = temp_file("vectors.json", DATA);
+    let vectors = temp_file("vectors.jsonl", "");
+    let output = run(
+        &[
+            "--data",
+            &data,
+            "--idf-log-base",
+            "2",
+            "--export-vectors",
+            &vectors,
+        ],
+        "",
+    );
+    assert!(output.status.success());
+
+    // ln(5) / ln(2) for "cam", converted once when exporting and not again when loading
+    let expected = "Highest IDF (rarest terms):\n  cam                              2.3219\n";
+    for extra in [&[][..], &["--idf-log-base", "2"][..]] {
+        let mut args = vec!["--data", &vectors, "--idf-table", "1"];
+        args.extend(extra);
+        let output = run(&args, "");
+        assert!(output.status.success());
+        assert!(stdout(&output).starts_with(expected), "{}", stdout(&output));
+    }
+}
+
+#[test]
+fn closed_stdout_ends_quietly() {
+    use std::io::{BufRead, BufReader};
+
+    let data = temp_file("broken-pipe.json", DATA);
+    let queries = temp_file("broken-pipe.txt", "what does eva do\n".repeat(5000));
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ThoughtfulAI"))
+        .args(["--data", &data, "--batch", &queries])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    // Read one line, then hang up like `head -n 1` does
+    let mut first_line = String::new();
+    BufReader::new(child.stdout.take().unwrap())
+        .read_line(&mut first_line)
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+
+    assert!(first_line.contains("EVA verifies patient eligibility."));
+    assert!(output.status.success());
+    assert!(!String::from_utf8_lossy(&output.stderr).contains("panicked"));
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn failed_stdout_is_an_error() {
+    // Every write to /dev/full fails with "no space left on device"
+    let output = Command::new(env!("CARGO_BIN_EXE_ThoughtfulAI"))
+        .arg("--version")
+        .stdout(std::fs::File::create("/dev/full").unwrap())
+        .output()
+        .unwrap();
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr.contains("could not write to stdout"));
+    assert!(!stderr.contains("panicked"));
+}
+
+#[test]
+fn answers_are_cleaned_only_when_asked() {
+    let data = temp_file(
+        "messy.json",
+        r#"{"questions": [{"question": "What does EVA do?",
+            "answer": "  EVA — our   eligibility agent – verifies\n coverage. "}]}"#,
+    );
+    let export = |name: &str, extra: &[&str]| {
+        let path = temp_file(name, "");
+        let mut args = vec!["--data", &data, "--export-jsonl", &path];
+        args.extend(extra);
+        assert!(run(&args, "").status.success());
+        std::fs::read_to_string(&path).unwrap()
+    };
+
+    assert_eq!(
+        export("cleaned.jsonl", &["--normalize-answers"]),
+        "{\"answer\":\"EVA - our eligibility agent - verifies coverage.\",\"question\":\"What does EVA do?\"}\n"
+    );
+    assert_eq!(
+        export("raw.jsonl", &[]),
+        "{\"answer\":\"  EVA — our   eligibility agent – verifies\\n coverage. \",\"question\":\"What does EVA do?\"}\n"
+    );
+}
+
+#[test]
+fn text_batch_pipes_one_answer_per_line() {
+    let data = temp_file("pipe.json", DATA);
+    let output = run(
+        &["--data", &data, "--batch", "-", "--format", "text"],
+        "What does EVA do?\nHow does PHIL work?\ntell me a joke\n",
+    );
+
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        "EVA verifies patient eligibility.\nPHIL posts payments.\n\
+         I'm sorry, I couldn't find a relevant question. Please try rephrasing your question.\n"
+    );
+}
+
+#[test]
+fn single_question_keeps_its_flat_idf_in_any_log_base() {
+    let data = temp_file(
+        "single.json",
+        r#"{"questions": [{"question": "What does EVA do?", "answer": "EVA verifies patient eligibility."}]}"#,
+    );
+    let output = run(
+        &["--data", &data, "--idf-log-base", "2", "--idf-table", "1"],
+        "",
+    );
+
+    assert!(output.status.success());
+    assert!(stdout(&output).contains("1.0000\n"));
+    assert!(!stdout(&output).contains("1.4427"));
+}
+
+#[test]
+fn stats_json_is_one_object_of_corpus_metrics() {
+    let data = temp_file("stats.json", DATA);
+    let output = run(&["--data", &data, "--stats-json"], "");
+
+    assert!(output.status.success());
+    let stats: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    for key in [
+        "question_count",
+        "vocabulary_size",
+        "avg_question_tokens",
+        "avg_answer_chars",
+        "question_tokens",
+        "answer_chars",
+        "top_terms",
+    ] {
+        assert!(stats.get(key).is_some(), "missing {}", key);
+    }
+    assert_eq!(stats["question_count"], 5);
+    assert!(stats["question_tokens"]["p50"].is_number());
+}
+
+#[test]
+fn cross_validation_needs_between_two_folds_and_one_per_question() {
+    let data = temp_file("cross-validate.json", DATA);
+    for folds in ["1", "6"] {
+        let output = run(&["--data", &data, "--cross-validate", folds], "");
+
+        assert_eq!(output.status.code(), Some(2));
+        assert!(String::from_utf8_lossy(&output.stderr)
+            .contains("cross-validation needs between 2 and 5 folds"));
+    }
+
+    let output = run(&["--data", &data, "--cross-validate", "5"], "");
+    assert!(output.status.success());
+    assert!(stdout(&output).starts_with("5-fold cross-validation: mean precision@1 0.000\n"));
+}
+
+#[test]
+fn search_results_expand_links() {
+    let data = temp_file(
+        "search-links.json",
+        r#"{"questions": [
+            {"question": "What is the refund policy?", "answer": "Refunds are issued within 30 days."},
+            {"question": "How do I cancel?", "answer": "Cancel anytime. [[What is the refund policy?]]"}
+        ]}"#,
+    );
+    let output = run(
+        &["--data", &data, "--prompt", ""],
+        "/search anytime\nexit\n",
+    );
+
+    assert!(stdout(&output)
+        .contains("Q: How do I cancel?\nA: Cancel anytime. Refunds are issued within 30 days.\n"));
+}